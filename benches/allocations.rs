@@ -0,0 +1,44 @@
+use rickroll::run_source;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+mod common;
+
+/*
+ * Counts the heap allocations of one run of each benchmarked program,
+ * with `cargo bench --bench allocations`.
+ * This is kept apart from the timed benchmarks, so counting doesn't slow them down.
+ */
+
+// the system allocator, counting every allocation
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn main() {
+    for program in common::programs().iter() {
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+        let output = match run_source(&program.source, "") {
+            Ok(output) => output,
+            Err(err) => panic!("{} failed:\n{}", program.name, err),
+        };
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+        assert_eq!(output, program.expected, "wrong output from {}", program.name);
+        println!("{:<16} {:>9} allocations", program.name, allocations);
+    }
+}
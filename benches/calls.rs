@@ -0,0 +1,22 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use rickroll::run_source;
+
+/*
+ * Times deep recursion through a verse with a long body, with `cargo bench --bench calls`.
+ * Function bodies are shared between calls, so the time of a call
+ * shouldn't grow with the size of the body it runs.
+ */
+
+fn calls(c: &mut Criterion) {
+    let source = include_str!("programs/calls.rick");
+    c.bench_function("recursive calls", |b| {
+        b.iter(|| {
+            let output = run_source(source, "").expect("calls.rick failed");
+            assert_eq!(output, "30380\n");
+        })
+    });
+}
+
+criterion_group!(benches, calls);
+criterion_main!(benches);
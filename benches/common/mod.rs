@@ -0,0 +1,49 @@
+/*
+ * The programs timed by `cargo bench`, shared by the benchmarks that run all of them.
+ */
+
+// number of times each line of the operator-heavy program is repeated
+const OPERATOR_LINES: usize = 1000;
+
+pub struct Program {
+    pub name: &'static str,
+    pub source: String,
+    pub expected: &'static str,
+}
+
+// a long program of operator-heavy expressions, which mostly measures lexing and parsing
+// every line leaves the variables unchanged, so the output doesn't depend on its length
+fn operators_source() -> String {
+    let mut source = String::from(include_str!("../programs/operators.rick"));
+    for _ in 0..OPERATOR_LINES {
+        source += "Never gonna give x (x * 3 + 7 - 7) / 3 % 1000 + (1 << 2 >> 2) - 1 + ~2 + 2 ** 1 & 1023 | 0 ^ 0\n";
+        source += "Never gonna give ok ok && x >= 0 || !(x < 0) and x != 1 + 2 * 3 - ((4 in ArrayOf(1, 2)) as INT)\n";
+    }
+    source += "Never gonna say x\nNever gonna say ok\n";
+    return source;
+}
+
+pub fn programs() -> Vec<Program> {
+    return vec![
+        Program {
+            name: "recursive fib",
+            source: String::from(include_str!("../programs/fib.rick")),
+            expected: "17711\n",
+        },
+        Program {
+            name: "array sort",
+            source: String::from(include_str!("../programs/sort.rick")),
+            expected: "0\n504\n996\n",
+        },
+        Program {
+            name: "tight loop",
+            source: String::from(include_str!("../programs/loop.rick")),
+            expected: "76290\n",
+        },
+        Program {
+            name: "operators",
+            source: operators_source(),
+            expected: "5\nTRUE\n",
+        },
+    ];
+}
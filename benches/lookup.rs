@@ -0,0 +1,22 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use rickroll::run_source;
+
+/*
+ * Times reading variables declared several contexts out, with `cargo bench --bench lookup`.
+ * Variables are found by the slots the parser gave them, so the time of a lookup
+ * shouldn't grow with the number of variables or contexts in the scope.
+ */
+
+fn lookup(c: &mut Criterion) {
+    let source = include_str!("programs/lookup.rick");
+    c.bench_function("nested lookups", |b| {
+        b.iter(|| {
+            let output = run_source(source, "").expect("lookup.rick failed");
+            assert_eq!(output, "720000\n");
+        })
+    });
+}
+
+criterion_group!(benches, lookup);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use rickroll::run_source;
+
+mod common;
+
+/*
+ * Times a few representative programs with `cargo bench`.
+ * Every run is checked against the expected output of its program,
+ * so a change that breaks a program can't look like a speedup.
+ */
+
+fn programs(c: &mut Criterion) {
+    for program in common::programs() {
+        c.bench_function(program.name, |b| {
+            b.iter(|| {
+                let output = match run_source(&program.source, "") {
+                    Ok(output) => output,
+                    Err(err) => panic!("{} failed:\n{}", program.name, err),
+                };
+                assert_eq!(output, program.expected, "wrong output from {}", program.name);
+            })
+        });
+    }
+}
+
+criterion_group!(benches, programs);
+criterion_main!(benches);
@@ -1,5 +1,8 @@
 use std::fmt::Display;
 
+// maximum number of tracebacks printed for an error
+pub const MAX_UNWIND_LIMIT: usize = 8;
+
 // all native error types
 #[derive(Debug)]
 pub enum ErrorType {
@@ -13,6 +16,8 @@ pub enum ErrorType {
     NameError,
     Traceback,
     StackOverflowError,
+    ValueError,
+    ProgramExit(i32), // not a failure, stops the program with a status (from Exit)
 }
 
 // get name from enum member
@@ -30,6 +35,8 @@ impl ErrorType {
             NameError => "Name Error",
             Traceback => "Traceback",
             StackOverflowError => "Stack Overflow",
+            ValueError => "Value Error",
+            ProgramExit(_) => "Program Exit",
         }
         .to_string()
     }
@@ -72,25 +79,75 @@ impl Error {
     }
 }
 
-impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut res = String::new();
-        // child if traceback
-        if self.child.is_some() {
-            // get previous error message from Box<Option<Error>>
-            res += &(self.child.as_ref().as_ref().unwrap().to_string())[..];
-            res += "\n";
+impl Error {
+    // description of the original error, below any tracebacks
+    pub fn message(&self) -> &str {
+        match self.child.as_ref() {
+            Some(child) => child.message(),
+            None => &self.desc,
         }
+    }
+
+    // the status passed to Exit, if the error stands for a call to it
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.child.as_ref() {
+            Some(child) => child.exit_code(),
+            None => match self.err {
+                ErrorType::ProgramExit(code) => Some(code),
+                _ => None,
+            },
+        }
+    }
+
+    // line of the outermost traceback or error
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    // the line describing this error alone, without its child
+    fn describe(&self) -> String {
         // error name
-        res += &self.err.as_string()[..];
+        let mut res = self.err.as_string();
         // error line if exists
-        if self.line.is_some() {
-            res = format!("{} on line {}", res, self.line.unwrap());
+        if let Some(line) = self.line {
+            res = format!("{} on line {}", res, line);
         }
         // error description if not traceback
         if self.child.is_none() {
             res = format!("{}: {}", res, self.desc);
         }
-        write!(f, "{}", res)
+        return res;
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // collect the chain of tracebacks down to the original error
+        let mut chain: Vec<&Error> = Vec::new();
+        let mut cur = Some(self);
+        while let Some(error) = cur {
+            chain.push(error);
+            cur = error.child.as_ref().as_ref();
+        }
+        // the original error comes first, followed by at most MAX_UNWIND_LIMIT tracebacks
+        let mut lines: Vec<String> = Vec::new();
+        for (ind, error) in chain.iter().rev().enumerate() {
+            if ind > MAX_UNWIND_LIMIT {
+                lines.push(format!("... {} more", chain.len() - ind));
+                break;
+            }
+            lines.push(error.describe());
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl std::error::Error for Error {
+    // a traceback's source is the error it wraps
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.child.as_ref() {
+            Some(child) => Some(child),
+            None => None,
+        }
     }
 }
@@ -1,15 +1,51 @@
 use crate::error::*;
 use crate::lexer::Token;
+use crate::stdlib::{Builtins, BUILTIN_FUNCTIONS};
 use crate::util::*;
 
+use std::collections::HashSet;
+use std::rc::Rc;
+
 // special operator characters
-const OP_CHARS: &str = "!&|<>=~";
+const OP_CHARS: &str = "!&|^<>=~";
 
 #[derive(Debug, Clone)]
 pub enum Expr {
     Value(RickrollObject),
-    Name(String),
+    Name(Var),
     Operation(Operator, Vec<Expr>),
+    Call(String, Vec<Expr>), // function name and arguments
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>), // condition and the values if it is TRUE or FALSE
+}
+
+impl Expr {
+    // whether the expression reads the variable
+    pub fn uses_var(&self, name: &str) -> bool {
+        match self {
+            Expr::Value(_) => return false,
+            Expr::Name(var) => return var.name == name,
+            Expr::Operation(_, args) | Expr::Call(_, args) => {
+                return args.iter().any(|x| x.uses_var(name));
+            }
+            Expr::Conditional(cond, then, other) => {
+                return cond.uses_var(name) || then.uses_var(name) || other.uses_var(name);
+            }
+        }
+    }
+}
+
+// literals are lexed like in expressions, so this lives with the lexer rather than in util
+impl RickrollObject {
+    // parses a single INT, FLOAT, BOOL, CHAR or UNDEFINED literal, as written in a program
+    // returns None for anything else, including arrays and negative numbers
+    pub fn from_literal(literal: &str) -> Option<RickrollObject> {
+        let tokens = ExprLexer::new(String::from(literal), 0).make_tokens().ok()?;
+        match &tokens[..] {
+            [Token::Value(_, RickrollObject::Array(_))] => return None,
+            [Token::Value(_, obj)] => return Some(obj.clone()),
+            _ => return None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -96,11 +132,7 @@ impl ExprLexer {
                             None,
                         ));
                     }
-                    chr = self.raw[self.ptr]; // cur char
-                    chrlit = match chr {
-                        'n' => '\n', // new line
-                        _ => chr,    // otherwise no escape sequence found, regular char
-                    };
+                    chrlit = self.make_escape()?;
                 }
                 self.ptr += 1;
                 if !self.has_more() {
@@ -125,14 +157,29 @@ impl ExprLexer {
                 self.ptr += 1;
                 continue;
             }
+            // string literal
+            if chr == '"' {
+                let string = self.make_string()?;
+                self.tokens.push(string);
+                continue;
+            }
             match chr {
                 // whitespace can be ignored
                 chr if chr.is_whitespace() => (),
-                '+' | '-' | '*' | '/' | '%' | ':' => self
-                    .tokens
-                    .push(Token::Operator(self.line, String::from(chr))),
+                // exponentiation
+                '*' if self.ptr + 1 < self.raw.len() && self.raw[self.ptr + 1] == '*' => {
+                    self.tokens
+                        .push(Token::Operator(self.line, Operator::Power));
+                    self.ptr += 1;
+                }
+                '+' | '-' | '*' | '/' | '%' | ':' => {
+                    let op = get_operator(&chr.to_string())?;
+                    self.tokens.push(Token::Operator(self.line, op));
+                }
                 '(' => self.tokens.push(Token::Punc(self.line, String::from("("))),
                 ')' => self.tokens.push(Token::Punc(self.line, String::from(")"))),
+                ',' => self.tokens.push(Token::Punc(self.line, String::from(","))),
+                '?' => self.tokens.push(Token::Punc(self.line, String::from("?"))),
                 _ => {
                     return Err(Error::new(
                         ErrorType::IllegalCharError,
@@ -146,6 +193,91 @@ impl ExprLexer {
         return Ok(self.tokens);
     }
 
+    // translates the escape sequence whose first character is at ptr (after the "\\")
+    // leaves ptr at the last character of the sequence
+    fn make_escape(&mut self) -> Result<char, Error> {
+        let chr = self.raw[self.ptr];
+        return match chr {
+            'n' => Ok('\n'), // new line
+            't' => Ok('\t'), // tab
+            'r' => Ok('\r'), // carriage return
+            '0' => Ok('\0'), // null
+            'x' => {
+                // exactly two hex digits (\x41)
+                let digits: String = self.raw[(self.ptr + 1)..].iter().take(2).collect();
+                if digits.len() != 2 || !digits.chars().all(|x| x.is_ascii_hexdigit()) {
+                    return Err(Error::new(
+                        ErrorType::IllegalCharError,
+                        "Expected two hex digits after \\x",
+                        None,
+                    ));
+                }
+                self.ptr += 2;
+                Ok(char::from(u8::from_str_radix(&digits, 16).unwrap()))
+            }
+            'u' => {
+                // one to six hex digits in braces (\u{1F600})
+                let rest: String = self.raw[(self.ptr + 1)..].iter().collect();
+                let end = rest.find('}');
+                let code = match end {
+                    Some(end) if rest.starts_with('{') && (2..=7).contains(&end) => {
+                        u32::from_str_radix(&rest[1..end], 16).ok()
+                    }
+                    _ => None,
+                };
+                match code.and_then(char::from_u32) {
+                    Some(res) => {
+                        self.ptr += rest[..=end.unwrap()].chars().count();
+                        Ok(res)
+                    }
+                    None => Err(Error::new(
+                        ErrorType::IllegalCharError,
+                        "Invalid unicode escape, expected \\u{...} with a valid code point",
+                        None,
+                    )),
+                }
+            }
+            // otherwise no escape sequence found, regular char
+            // this covers \\, \' and \"
+            _ => Ok(chr),
+        };
+    }
+
+    // makes a string literal (an array of chars) starting at ptr
+    fn make_string(&mut self) -> Result<Token, Error> {
+        let mut chars = Vec::new();
+        self.ptr += 1; // opening quote
+        loop {
+            if !self.has_more() {
+                return Err(Error::new(
+                    ErrorType::IllegalCharError,
+                    "Unterminated string literal",
+                    None,
+                ));
+            }
+            let mut chr = self.raw[self.ptr];
+            if chr == '"' {
+                break;
+            }
+            // possible escape sequence
+            if chr == '\\' {
+                self.ptr += 1;
+                if !self.has_more() {
+                    return Err(Error::new(
+                        ErrorType::IllegalCharError,
+                        "Unterminated string literal",
+                        None,
+                    ));
+                }
+                chr = self.make_escape()?;
+            }
+            chars.push(RickrollObject::Char(chr));
+            self.ptr += 1;
+        }
+        self.ptr += 1; // closing quote
+        return Ok(Token::Value(self.line, RickrollObject::Array(Rc::new(chars))));
+    }
+
     // parses a number starting at self.ptr
     fn make_number(&mut self) -> Result<Token, Error> {
         let mut float = false;
@@ -210,8 +342,8 @@ impl ExprLexer {
             self.ptr += 1;
             if self.has_more() {
                 let cur = self.raw[self.ptr];
-                // can only be alphabetic or _
-                if cur.is_ascii_alphabetic() || cur == '_' {
+                // can only be alphabetic, a digit or _
+                if cur.is_ascii_alphanumeric() || cur == '_' {
                     chr = cur;
                 } else {
                     break;
@@ -220,15 +352,51 @@ impl ExprLexer {
                 break;
             }
         }
-        // check if var is a constant
-        let res = from_constant(&varname);
-        if res.is_some() {
-            return Ok(Token::Value(self.line, res.unwrap()));
+        if varname == "as" {
+            return self.make_cast();
+        }
+        // check if var is a constant or a word operator
+        if let Some(res) = from_constant(&varname) {
+            return Ok(Token::Value(self.line, res));
+        } else if let Some(op) = from_word_operator(&varname) {
+            return Ok(Token::Operator(self.line, get_operator(op)?));
         } else {
             return Ok(Token::Name(self.line, varname));
         }
     }
 
+    // makes a cast operator from the type after "as", which ends at ptr
+    fn make_cast(&mut self) -> Result<Token, Error> {
+        while self.has_more() && self.raw[self.ptr].is_whitespace() {
+            self.ptr += 1;
+        }
+        let mut type_name = String::new();
+        while self.has_more() && self.raw[self.ptr].is_ascii_alphanumeric() {
+            type_name.push(self.raw[self.ptr]);
+            self.ptr += 1;
+        }
+        match &type_name[..] {
+            "INT" | "FLOAT" | "CHAR" => {
+                let op = get_operator(&format!("as {}", type_name))?;
+                return Ok(Token::Operator(self.line, op));
+            }
+            "" => {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    "Expected a type after as",
+                    None,
+                ))
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    &format!("Cannot cast to {}", type_name)[..],
+                    None,
+                ))
+            }
+        }
+    }
+
     // makes a complex operator starting at ptr
     fn make_operator(&mut self) -> Result<Token, Error> {
         let mut opname = String::new();
@@ -238,6 +406,11 @@ impl ExprLexer {
             self.ptr += 1;
             if self.has_more() {
                 let cur = self.raw[self.ptr];
+                // "!" and "~" only start operators, so "!!" is two operators
+                // "=!" is still scanned to suggest "!="
+                if (cur == '!' || cur == '~') && opname != "=" {
+                    break;
+                }
                 if OP_CHARS.contains(cur) {
                     chr = cur;
                 } else {
@@ -248,24 +421,33 @@ impl ExprLexer {
             }
         }
         return match &opname[..] {
-            // support only one "!" before an argument
-            // multiple "!" can be formatted as "! !"
-            "&&" | "||" | ">" | "<" | ">=" | "<=" | "==" | "!=" | "!" | "~" => {
-                Ok(Token::Operator(self.line, opname))
-            }
-            _ => Err(Error::new(
-                ErrorType::RuntimeError,
-                &format!("Operator {} not found", opname).to_string(),
-                None,
-            )),
+            "&&" | "||" | ">" | "<" | ">=" | "<=" | "==" | "!=" | "!" | "~" | "&" | "|" | "^" | "<<"
+            | ">>" => {
+                Ok(Token::Operator(self.line, get_operator(&opname)?))
+            }
+            _ => {
+                // suggest a fix for common transpositions
+                let suggestion = match &opname[..] {
+                    "=<" => Some("<="),
+                    "=>" => Some(">="),
+                    "=!" => Some("!="),
+                    _ => None,
+                };
+                let desc = match suggestion {
+                    Some(fix) => format!("Operator {} not found, did you mean {}?", opname, fix),
+                    None => format!("Operator {} not found", opname),
+                };
+                Err(Error::new(ErrorType::RuntimeError, &desc[..], None))
+            }
         };
     }
 }
 
 // get operator from string
-pub fn get_operator(str: &String) -> Result<Operator, Error> {
+// only used when tokenizing, since operator tokens hold the resolved operator
+pub fn get_operator(str: &str) -> Result<Operator, Error> {
     use Operator::*;
-    return match &str[..] {
+    return match str {
         "||" => Ok(Or),
         "&&" => Ok(And),
         ">" => Ok(Greater),
@@ -279,9 +461,20 @@ pub fn get_operator(str: &String) -> Result<Operator, Error> {
         "*" => Ok(Multiply),
         "/" => Ok(Divide),
         "%" => Ok(Modulo),
+        "**" => Ok(Power),
         ":" => Ok(ArrayAccess),
         "!" => Ok(Not),
         "~" => Ok(UnaryMinus),
+        "&" => Ok(BitAnd),
+        "|" => Ok(BitOr),
+        "^" => Ok(BitXor),
+        "<<" => Ok(ShiftLeft),
+        ">>" => Ok(ShiftRight),
+        "in" => Ok(In),
+        "compl" => Ok(BitNot),
+        "as INT" => Ok(CastInt),
+        "as FLOAT" => Ok(CastFloat),
+        "as CHAR" => Ok(CastChar),
         _ => Err(Error::new(
             ErrorType::SyntaxError,
             &format!("Operator {} not found", str)[..],
@@ -297,15 +490,67 @@ pub fn precedence_of(op: &Operator) -> usize {
     return match op {
         Or => 1,
         And => 2,
-        Greater | Less | GreaterEquals | LessEquals | Equals | NotEquals => 3,
-        Add | Subtract => 4,
-        Multiply | Divide | Modulo => 5,
-        ArrayAccess => 6,
-        Not => 7,
-        UnaryMinus => 8,
+        Greater | Less | GreaterEquals | LessEquals | Equals | NotEquals | In => 3,
+        BitOr => 4,
+        BitXor => 5,
+        BitAnd => 6,
+        ShiftLeft | ShiftRight => 7,
+        Add | Subtract => 8,
+        Multiply | Divide | Modulo => 9,
+        Power => 10,
+        CastInt | CastFloat | CastChar => 11,
+        ArrayAccess => 12,
+        Not | BitNot => 13,
+        UnaryMinus => 14,
     };
 }
 
+// tokens of the condition and of the two values of a conditional expression
+type ConditionalParts = (Vec<Token>, Vec<Token>, Vec<Token>);
+
+// splits the tokens of a conditional expression (cond ? a : b) into its three parts
+// returns None if there is no "?" outside of parentheses
+// the "?" binds loosest, and the first ":" that doesn't belong to a nested conditional ends the
+// first value, so array access in that value needs parentheses
+fn split_conditional(tokens: &[Token]) -> Result<Option<ConditionalParts>, Error> {
+    let mut depth = 0;
+    let mut question = None;
+    let mut nested = 0; // conditionals in the first value that are still missing a ":"
+    for (ind, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punc(_, punc) if punc == "(" => depth += 1,
+            Token::Punc(_, punc) if punc == ")" => depth -= 1,
+            Token::Punc(_, punc) if punc == "?" && depth == 0 => {
+                if question.is_none() {
+                    question = Some(ind);
+                } else {
+                    nested += 1;
+                }
+            }
+            Token::Operator(_, Operator::ArrayAccess) if depth == 0 && question.is_some() => {
+                if nested == 0 {
+                    let question = question.unwrap();
+                    return Ok(Some((
+                        tokens[..question].to_vec(),
+                        tokens[(question + 1)..ind].to_vec(),
+                        tokens[(ind + 1)..].to_vec(),
+                    )));
+                }
+                nested -= 1;
+            }
+            _ => (),
+        }
+    }
+    if question.is_some() {
+        return Err(Error::new(
+            ErrorType::SyntaxError,
+            "Expected : after ?",
+            None,
+        ));
+    }
+    return Ok(None);
+}
+
 /*
  * This expression parser utilizes Dijkstra's Shunting-yard algorithm
  * for parsing infix expressions and converting them to ASTs.
@@ -317,8 +562,11 @@ pub struct ExprParser {
     tokens: Vec<Token>,
     ptr: usize,
     scope: Scope,
+    functions: HashSet<String>, // user functions that can be called
+    builtins: Builtins,         // custom builtins that can be called
     output_stack: Vec<Token>, // output stack
     op_stack: Vec<Token>,     // stack of operators and parenthesis
+    calls: Vec<Expr>,         // parsed calls and conditionals, in the order of their "()" tokens in the output
 }
 
 impl ExprParser {
@@ -328,26 +576,144 @@ impl ExprParser {
             ptr: 0,
             output_stack: Vec::new(),
             op_stack: Vec::new(),
+            calls: Vec::new(),
             scope,
+            functions: HashSet::new(),
+            builtins: Builtins::new(),
+        }
+    }
+
+    // sets the user functions that can be called in the expression
+    // their number of arguments is only checked when they are called
+    pub fn with_functions(mut self, functions: HashSet<String>) -> ExprParser {
+        self.functions = functions;
+        self
+    }
+
+    // allows calls to custom builtins, see Parser::with_builtins
+    pub fn with_builtins(mut self, builtins: &Builtins) -> ExprParser {
+        self.builtins = builtins.clone();
+        self
+    }
+
+    // parses a call whose name is at ptr and "(" right after it
+    // leaves ptr at the closing ")"
+    fn make_call(&mut self, name: &str) -> Result<Expr, Error> {
+        if !self.functions.contains(name) && !self.builtins.contains(name) && !BUILTIN_FUNCTIONS.contains_key(name) {
+            return Err(Error::new(
+                ErrorType::NameError,
+                &format!("Function name {} doesn't exist", name)[..],
+                None,
+            ));
+        }
+        self.ptr += 2;
+        // split the tokens up to the matching ")" at every top level ","
+        let mut groups: Vec<Vec<Token>> = vec![Vec::new()];
+        let mut depth = 0;
+        loop {
+            if !self.has_more() {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    &format!("Unclosed call to {}", name)[..],
+                    None,
+                ));
+            }
+            let token = self.tokens[self.ptr].clone();
+            if let Token::Punc(_, punc) = &token {
+                match &punc[..] {
+                    "(" => depth += 1,
+                    ")" if depth == 0 => break,
+                    ")" => depth -= 1,
+                    "," if depth == 0 => {
+                        groups.push(Vec::new());
+                        self.ptr += 1;
+                        continue;
+                    }
+                    _ => (),
+                }
+            }
+            groups.last_mut().unwrap().push(token);
+            self.ptr += 1;
+        }
+        let mut args: Vec<Expr> = Vec::new();
+        // empty parentheses pass no arguments
+        if groups.len() > 1 || !groups[0].is_empty() {
+            for group in groups {
+                args.push(self.parse_part(group)?);
+            }
         }
+        // user functions shadow builtins
+        if !self.functions.contains(name) {
+            self.builtins.check_arity(name, args.len(), None)?;
+        }
+        return Ok(Expr::Call(String::from(name), args));
     }
 
     fn has_more(&self) -> bool {
         self.ptr < self.tokens.len()
     }
 
+    // parses the tokens as a separate expression
+    fn parse_part(&self, tokens: Vec<Token>) -> Result<Expr, Error> {
+        return ExprParser::new(tokens, self.scope.clone())
+            .with_functions(self.functions.clone())
+            .with_builtins(&self.builtins)
+            .parse();
+    }
+
+    // parses a conditional in the parentheses starting at ptr
+    // leaves ptr at the closing ")" if there is one, otherwise doesn't move it
+    fn make_grouped_conditional(&mut self) -> Result<Option<Expr>, Error> {
+        let mut depth = 0;
+        let mut end = None;
+        for ind in self.ptr..self.tokens.len() {
+            if let Token::Punc(_, punc) = &self.tokens[ind] {
+                match &punc[..] {
+                    "(" => depth += 1,
+                    ")" => depth -= 1,
+                    _ => (),
+                }
+                if depth == 0 {
+                    end = Some(ind);
+                    break;
+                }
+            }
+        }
+        // unclosed parentheses are reported by the rest of the parser
+        let end = match end {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+        match split_conditional(&self.tokens[(self.ptr + 1)..end])? {
+            Some((cond, then, other)) => {
+                let conditional = Expr::Conditional(
+                    Box::new(self.parse_part(cond)?),
+                    Box::new(self.parse_part(then)?),
+                    Box::new(self.parse_part(other)?),
+                );
+                self.ptr = end;
+                return Ok(Some(conditional));
+            }
+            None => return Ok(None),
+        }
+    }
+
     // resolves as many operations as possible given the last operator
     // all operators are left-associative
-    fn pop(&mut self, op: &Operator) -> Result<(), Error> {
+    fn pop(&mut self, op: &Operator) {
         while !self.op_stack.is_empty() {
             let top = self.op_stack.last().unwrap();
             if let Token::Punc(_, _) = top {
                 break; // will never be ")", only "("
             }
             match top {
-                Token::Operator(_, top_chr) => {
+                Token::Operator(_, top_op) => {
                     // break if precedence is lower
-                    if precedence_of(&get_operator(top_chr)?) < precedence_of(op) {
+                    // or equal, for right-associative operators
+                    let top_prec = precedence_of(top_op);
+                    if top_prec < precedence_of(op)
+                        || (top_prec == precedence_of(op) && op.is_right_assoc())
+                    {
                         break;
                     }
                     self.output_stack.push(self.op_stack.pop().unwrap());
@@ -355,55 +721,78 @@ impl ExprParser {
                 _ => panic!("ExprParser::pop called with non punctuation or operator"),
             }
         }
-        return Ok(());
     }
 
     // resolves all operations until there are no more operators
     // or a left parenthesis is reached
-    fn pop_all(&mut self) -> Result<(), Error> {
-        while !self.op_stack.is_empty() {
-            let top = self.op_stack.pop().unwrap(); // pop top
+    fn pop_all(&mut self) {
+        while let Some(top) = self.op_stack.pop() {
             if let Token::Punc(_, _) = top {
                 break; // will never be ")", only "("
             }
             match &top {
-                Token::Operator(_, op) => {
-                    get_operator(&op)?; // ensure operator is valid
+                Token::Operator(_, _) => {
                     self.output_stack.push(top);
                 }
                 _ => panic!("ExprParser::pop_all called with non punctuation or operator"),
             }
         }
-        return Ok(());
     }
 
     // parses the tokens into RPN stored in output_stacl
-    fn to_rpn(&mut self) -> Result<(), Error> {
+    fn make_rpn(&mut self) -> Result<(), Error> {
         while self.has_more() {
-            let token = ((&self.tokens)[self.ptr]).clone(); // reference to a Token object
+            let token = self.tokens[self.ptr].clone();
             match &token {
                 Token::Value(_, _) => self.output_stack.push(token),
                 Token::Operator(_, op) => {
-                    let valid = get_operator(&op)?;
-                    if !valid.is_unary() {
-                        self.pop(&valid)?;
+                    let valid = *op;
+                    if valid.is_postfix() {
+                        // the operand is complete, so the operator goes straight to the output
+                        self.pop(&valid);
+                        self.output_stack.push(token);
+                    } else {
+                        if !valid.is_unary() {
+                            self.pop(&valid);
+                        }
+                        self.op_stack.push(token);
                     }
-                    self.op_stack.push(token);
                 }
                 Token::Punc(_, punc) => {
                     // "(" or ")"
                     match &punc[..] {
                         "(" => {
-                            self.op_stack.push(token);
+                            if let Some(conditional) = self.make_grouped_conditional()? {
+                                // like a call, the conditional is a single operand
+                                self.calls.push(conditional);
+                                self.output_stack.push(Token::Punc(token.get_line(), String::from("()")));
+                            } else {
+                                self.op_stack.push(token);
+                            }
                         }
                         ")" => {
-                            self.pop_all()?;
+                            self.pop_all();
+                        }
+                        _ => {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                &format!("Unexpected {} in expression", punc)[..],
+                                None,
+                            ));
                         }
-                        _ => panic!("Unexpected symbol found in ExprParser::to_rpn"),
                     }
                 }
-                Token::Name(_, name) => {
-                    if self.scope.has_var(name.clone()) {
+                Token::Name(ln, name) => {
+                    let is_call = match self.tokens.get(self.ptr + 1) {
+                        Some(Token::Punc(_, punc)) => punc == "(",
+                        _ => false,
+                    };
+                    if is_call {
+                        // the call is a single operand, marked by "()" in the output
+                        let call = self.make_call(name)?;
+                        self.calls.push(call);
+                        self.output_stack.push(Token::Punc(*ln, String::from("()")));
+                    } else if self.scope.has_var(name.clone()) {
                         self.output_stack.push(token);
                     } else {
                         return Err(Error::new(
@@ -413,24 +802,34 @@ impl ExprParser {
                         ));
                     }
                 }
-                _ => panic!("Unexpected enum variant found in ExprParser::to_rpn"),
+                _ => panic!("Unexpected enum variant found in ExprParser::make_rpn"),
             }
             self.ptr += 1;
         }
         // try to pop all operations at the end
-        self.pop_all()?;
+        self.pop_all();
         return Ok(());
     }
 
     pub fn parse(mut self) -> Result<Expr, Error> {
-        self.to_rpn()?;
+        if let Some((cond, then, other)) = split_conditional(&self.tokens)? {
+            return Ok(Expr::Conditional(
+                Box::new(self.parse_part(cond)?),
+                Box::new(self.parse_part(then)?),
+                Box::new(self.parse_part(other)?),
+            ));
+        }
+        self.make_rpn()?;
         let mut stack: Vec<Expr> = Vec::new();
         if self.output_stack.len() == 1 {
             let tok = self.output_stack.pop().unwrap();
             if let Token::Name(_, name) = tok {
-                return Ok(Expr::Name(name));
+                let slot = self.scope.resolve(&name);
+                return Ok(Expr::Name(Var { name, slot }));
             } else if let Token::Value(_, val) = tok {
                 return Ok(Expr::Value(val));
+            } else if let Token::Punc(_, _) = tok {
+                return Ok(self.calls.pop().unwrap());
             } else {
                 return Err(Error::new(
                     ErrorType::SyntaxError,
@@ -439,8 +838,7 @@ impl ExprParser {
                 ));
             }
         }
-        while !self.output_stack.is_empty() {
-            let token = self.output_stack.pop().unwrap();
+        while let Some(token) = self.output_stack.pop() {
             match token {
                 Token::Value(_, obj) => {
                     let last = stack.last_mut().unwrap();
@@ -452,13 +850,25 @@ impl ExprParser {
                     }
                 }
                 Token::Operator(_, op) => {
-                    stack.push(Expr::Operation(get_operator(&op)?, Vec::new()));
+                    stack.push(Expr::Operation(op, Vec::new()));
                 }
                 Token::Name(_, name) => {
                     let last = stack.last_mut().unwrap();
                     match last {
                         Expr::Operation(_, args) => {
-                            args.push(Expr::Name(name.clone()));
+                            let slot = self.scope.resolve(&name);
+                            args.push(Expr::Name(Var { name, slot }));
+                        }
+                        _ => panic!("ExprParser::parse: Found non-operation in return stack"),
+                    }
+                }
+                // calls are popped in reverse, like the output
+                Token::Punc(_, _) => {
+                    let call = self.calls.pop().unwrap();
+                    let last = stack.last_mut().unwrap();
+                    match last {
+                        Expr::Operation(_, args) => {
+                            args.push(call);
                         }
                         _ => panic!("ExprParser::parse: Found non-operation in return stack"),
                     }
@@ -468,12 +878,11 @@ impl ExprParser {
             while stack.len() > 1 {
                 let top_expr = stack.last().unwrap();
                 if let Expr::Operation(op, args) = top_expr {
-                    let enough_args;
-                    if op.is_unary() {
-                        enough_args = args.len() == 1;
+                    let enough_args = if op.is_unary() {
+                        args.len() == 1
                     } else {
-                        enough_args = args.len() == 2;
-                    }
+                        args.len() == 2
+                    };
                     if enough_args {
                         let top_expr = stack.pop().unwrap();
                         if let Expr::Operation(_, args) = stack.last_mut().unwrap() {
@@ -481,6 +890,9 @@ impl ExprParser {
                         } else {
                             panic!("ExprParser::parse: Found non-operation in return stack");
                         }
+                    } else {
+                        // top operation still needs more operands
+                        break;
                     }
                 } else {
                     panic!("ExprParser::parse: Found non-operation in return stack");
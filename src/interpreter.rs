@@ -1,18 +1,93 @@
 use crate::error::*;
 use crate::expr::*;
+use crate::lexer::split_lines;
 use crate::parser::*;
 use crate::util::*;
-use crate::stdlib::BUILTIN_FUNCTIONS;
+use crate::stdlib::{cast, read_line_lossy, Builtins, LibFunction, BUILTIN_FUNCTIONS};
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::io::{BufRead, Write};
+use std::fmt;
+use std::rc::Rc;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::Arc;
 
+// maximum number of nested statements (function calls and blocks)
 pub const MAX_RECURSION_DEPTH: usize = 10000;
-pub const MAX_UNWIND_LIMIT: usize = 8;
+// the native stack is grown on the heap when less than STACK_RED_ZONE bytes are left
+// so MAX_RECURSION_DEPTH can be reached on any thread, even in debug builds
+const STACK_RED_ZONE: usize = 1024 * 1024;
+const STACK_SEGMENT_SIZE: usize = 16 * 1024 * 1024;
+
+// output kept apart from the program's own, such as stderr
+struct Diagnostics(Box<dyn Write>);
+
+impl fmt::Debug for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Diagnostics")
+    }
+}
+
+// writes every executed statement to a separate output
+#[derive(Debug)]
+struct Tracer {
+    lines: Vec<String>, // source code of the program
+    out: Diagnostics,
+}
+
+// called before each statement with its line, the variables in scope and the program's input
+// (so an interactive debugger can read from the same stream), returns false to stop the program
+pub type StatementHook = Box<dyn FnMut(usize, &Scope, &mut dyn BufRead) -> bool>;
+
+struct Hook(StatementHook);
+
+impl fmt::Debug for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Hook")
+    }
+}
 
 #[derive(Debug)]
 pub struct Interpreter {
-    functions: HashMap<String, ASTNode>,
+    functions: HashMap<String, Rc<ASTNode>>, // shared, so calls don't clone the bodies
+    allow_sleep: bool,    // whether Sleep may pause execution
+    int_conditions: bool, // whether INT conditions are truthy when nonzero
+    lossy_input: bool,    // whether ReadLine replaces invalid UTF-8
+    max_collection_size: Option<usize>, // maximum length of arrays made or stored by the program
+    max_memory: Option<usize>, // maximum estimated size of all variables in bytes
+    suspended_memory: usize, // size of the callers' contexts during a function call
+    depth: usize,         // number of statements currently executing
+    globals: Scope,       // global scope after the last run
+    exit_code: Option<i32>, // status passed to Exit, if the program called it
+    tracer: Option<Tracer>, // None unless statements are traced
+    undefined_warnings: Option<Diagnostics>, // where to warn about printing UNDEFINED
+    builtins: Builtins,   // custom builtins added by the embedder
+    printed: Option<Vec<(usize, RickrollObject)>>, // values of say statements, if collected
+    hook: Option<Hook>,   // called before each statement, for debuggers
+    interrupt: Option<Arc<AtomicBool>>, // set from outside to stop the program, such as on Ctrl-C
+}
+
+// control flow signal returned by an executed statement
+#[derive(Debug)]
+pub enum Signal {
+    Next, // continue with the next statement
+    Return(RickrollObject),
+    Break(Option<String>), // label of the loop to leave, None for the innermost one
+}
+
+// current value of a for loop's variable, which the body may have replaced
+fn loop_counter(scope: &Scope, var: &Var, ln: usize) -> Result<i32, Error> {
+    match scope.get(var) {
+        Some(RickrollObject::Int(x)) => return Ok(x),
+        _ => {
+            return Err(Error::new(
+                ErrorType::RuntimeError,
+                "For loop variable is not an integer",
+                Some(ln),
+            ))
+        }
+    }
 }
 
 fn eval_err(op: &Operator) -> Error {
@@ -23,40 +98,291 @@ fn eval_err(op: &Operator) -> Error {
     )
 }
 
+// wraps a traceback around a possible error
+fn wrap_check<T>(res: Result<T, Error>, ln: usize) -> Result<T, Error> {
+    if let Err(error) = res {
+        return Err(Error::traceback(error, Some(ln)));
+    }
+    return res;
+}
+
+// promotes an Int operand to Float if the other operand is a Float
+fn promote(first: RickrollObject, second: RickrollObject) -> (RickrollObject, RickrollObject) {
+    use RickrollObject::*;
+    match (first, second) {
+        (Int(x), Float(y)) => (Float(x as f32), Float(y)),
+        (Float(x), Int(y)) => (Float(x), Float(y as f32)),
+        other => other,
+    }
+}
+
+// orders two arrays element by element, a prefix of the other comes first
+// elements are compared like the operands of < and >, so they can't be chars or bools
+fn compare_arrays(first: &[RickrollObject], second: &[RickrollObject]) -> Result<Ordering, Error> {
+    use RickrollObject::*;
+    for (x, y) in first.iter().zip(second.iter()) {
+        let ord = match promote(x.clone(), y.clone()) {
+            (Int(x), Int(y)) => Some(x.cmp(&y)),
+            (Float(x), Float(y)) => x.partial_cmp(&y),
+            (Array(x), Array(y)) => Some(compare_arrays(&x, &y)?),
+            _ => None,
+        };
+        match ord {
+            Some(Ordering::Equal) => (),
+            Some(ord) => return Ok(ord),
+            None => {
+                return Err(Error::new(
+                    ErrorType::IllegalArgumentError,
+                    &format!("Cannot compare array elements {} and {}", x, y)[..],
+                    None,
+                ))
+            }
+        }
+    }
+    return Ok(first.len().cmp(&second.len()));
+}
+
 impl Interpreter {
     pub fn new(functions: HashMap<String, ASTNode>) -> Interpreter {
         Interpreter {
-            functions,
+            functions: functions.into_iter().map(|(name, function)| (name, Rc::new(function))).collect(),
+            allow_sleep: true,
+            int_conditions: false,
+            lossy_input: false,
+            max_collection_size: None,
+            max_memory: None,
+            suspended_memory: 0,
+            depth: 0,
+            globals: Scope::new(),
+            exit_code: None,
+            tracer: None,
+            undefined_warnings: None,
+            builtins: Builtins::new(),
+            printed: None,
+            hook: None,
+            interrupt: None,
         }
     }
 
-    // wraps a traceback around a possible error
-    fn wrap_check<T>(&self, res: Result<T, Error>, ln: usize) -> Result<T, Error> {
-        if let Err(error) = res {
-            return Err(Error::traceback(error, Some(ln)));
+    // enables or disables the Sleep builtin (for sandboxed runs)
+    pub fn with_sleep(mut self, allow_sleep: bool) -> Interpreter {
+        self.allow_sleep = allow_sleep;
+        self
+    }
+
+    // allows INT conditions, where zero is false and anything else is true
+    pub fn with_int_conditions(mut self, int_conditions: bool) -> Interpreter {
+        self.int_conditions = int_conditions;
+        self
+    }
+
+    // makes ReadLine replace invalid UTF-8 instead of throwing an error
+    pub fn with_lossy_input(mut self, lossy_input: bool) -> Interpreter {
+        self.lossy_input = lossy_input;
+        self
+    }
+
+    // limits the length of arrays and strings made or stored by the program (unlimited by default)
+    pub fn with_max_collection_size(mut self, max_collection_size: Option<usize>) -> Interpreter {
+        self.max_collection_size = max_collection_size;
+        self
+    }
+
+    // limits the estimated size of all live variables (unlimited by default)
+    pub fn with_max_memory(mut self, max_memory: Option<usize>) -> Interpreter {
+        self.max_memory = max_memory;
+        // the globals kept between interactive inputs are counted too
+        self.globals = Scope::from_vec(vec![self.new_context()]);
+        self
+    }
+
+    // adds native functions that programs can call like builtins
+    // they replace builtins of the same name, but user functions shadow them
+    // the same builtins must be passed to Parser::with_builtins
+    pub fn with_builtins(mut self, builtins: &Builtins) -> Interpreter {
+        self.builtins = builtins.clone();
+        self
+    }
+
+    // collects the value of every say statement along with its line
+    pub fn with_collect_printed(mut self, collect: bool) -> Interpreter {
+        self.printed = if collect { Some(Vec::new()) } else { None };
+        self
+    }
+
+    // values printed by say statements during the last call to run, in order
+    // empty unless enabled with with_collect_printed
+    pub fn printed_values(&self) -> &[(usize, RickrollObject)] {
+        match &self.printed {
+            Some(printed) => printed,
+            None => &[],
         }
-        return res;
     }
 
-    fn eval(&self, expr: &Expr, scope: &Scope) -> Result<RickrollObject, Error> {
+    // warns on out whenever a say statement prints UNDEFINED, which is usually a bug
+    pub fn with_undefined_warnings(mut self, out: Box<dyn Write>) -> Interpreter {
+        self.undefined_warnings = Some(Diagnostics(out));
+        self
+    }
+
+    // stops the program with an error once the flag is set, which is checked before each statement
+    // the flag can be set from another thread or a signal handler
+    pub fn with_interrupt(mut self, flag: Arc<AtomicBool>) -> Interpreter {
+        self.interrupt = Some(flag);
+        self
+    }
+
+    // calls the hook before executing each statement
+    pub fn with_statement_hook(mut self, hook: StatementHook) -> Interpreter {
+        self.hook = Some(Hook(hook));
+        self
+    }
+
+    // writes each statement's line and source to out as it executes
+    // statements with an expression are followed by its value
+    pub fn with_trace(mut self, source: &str, out: Box<dyn Write>) -> Interpreter {
+        self.tracer = Some(Tracer {
+            lines: split_lines(source),
+            out: Diagnostics(out),
+        });
+        self
+    }
+
+    fn trace_statement(&mut self, ln: usize) {
+        if let Some(tracer) = &mut self.tracer {
+            let src = tracer.lines.get(ln - 1).map_or("", |x| x.trim());
+            writeln!(tracer.out.0, "line {}: {}", ln, src).expect("Error when writing trace");
+        }
+    }
+
+    fn trace_value(&mut self, value: &RickrollObject) {
+        if let Some(tracer) = &mut self.tracer {
+            writeln!(tracer.out.0, "  => {}", value).expect("Error when writing trace");
+        }
+    }
+
+    // fails if the program was interrupted
+    fn check_interrupt(&self, ln: usize) -> Result<(), Error> {
+        if let Some(flag) = &self.interrupt {
+            if flag.load(atomic::Ordering::Relaxed) {
+                return Err(Error::new(ErrorType::RuntimeError, "Interrupted", Some(ln)));
+            }
+        }
+        return Ok(());
+    }
+
+    // checks that the variables in scope, along with the callers', fit in the budget
+    fn check_memory(&self, scope: &Scope) -> Result<(), Error> {
+        if let Some(max) = self.max_memory {
+            let used = self.suspended_memory + scope.size();
+            if used > max {
+                return Err(Error::new(
+                    ErrorType::RuntimeError,
+                    &format!("Memory usage of {} bytes exceeds the maximum of {}", used, max)[..],
+                    None,
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    // checks that a collection of the given length is within the limit
+    fn check_collection_size(&self, len: usize) -> Result<(), Error> {
+        if let Some(max) = self.max_collection_size {
+            if len > max {
+                return Err(Error::new(
+                    ErrorType::RuntimeError,
+                    &format!("Array length {} exceeds the maximum of {}", len, max)[..],
+                    None,
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    // checks the length of a value if it's an array
+    fn check_value_size(&self, obj: &RickrollObject) -> Result<(), Error> {
+        if let RickrollObject::Array(arr) = obj {
+            self.check_collection_size(arr.len())?;
+        }
+        return Ok(());
+    }
+
+    // an empty context, which only estimates its size when memory is limited
+    fn new_context(&self) -> Context {
+        return Context::new().with_size_tracking(self.max_memory.is_some());
+    }
+
+    // the truth value of a condition, None if it isn't a valid condition
+    fn truth_of(&self, obj: &RickrollObject) -> Option<bool> {
+        match obj {
+            RickrollObject::Bool(x) => Some(*x),
+            RickrollObject::Int(x) if self.int_conditions => Some(*x != 0),
+            _ => None,
+        }
+    }
+
+    fn eval(
+        &mut self,
+        expr: &Expr,
+        scope: &mut Scope,
+        buffer: &mut dyn Write,
+        reader: &mut dyn BufRead,
+    ) -> Result<RickrollObject, Error> {
+        // nested expressions recurse without going through execute
+        let res = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, || {
+            self.eval_expr(expr, scope, buffer, reader)
+        })?;
+        self.check_value_size(&res)?;
+        return Ok(res);
+    }
+
+    fn eval_expr(
+        &mut self,
+        expr: &Expr,
+        scope: &mut Scope,
+        buffer: &mut dyn Write,
+        reader: &mut dyn BufRead,
+    ) -> Result<RickrollObject, Error> {
         match expr {
             Expr::Value(obj) => Ok(obj.clone()),
-            Expr::Name(name) => {
-                if scope.has_var(name.clone()) {
-                    return Ok(scope.get_var(name.clone()).unwrap());
-                } else {
+            Expr::Name(var) => match scope.get(var) {
+                Some(val) => return Ok(val),
+                None => {
                     return Err(Error::new(
                         ErrorType::NameError,
-                        &format!("Variable {} doesn't exist", name)[..],
+                        &format!("Variable {} doesn't exist", var.name)[..],
                         None,
                     ));
                 }
+            },
+            Expr::Call(func, args) => {
+                let mut passed: Vec<RickrollObject> = Vec::new();
+                for arg in args {
+                    passed.push(self.eval(arg, scope, buffer, reader)?);
+                }
+                return self.call_function(func.clone(), passed, scope, buffer, reader);
+            }
+            Expr::Conditional(cond, then, other) => {
+                // only the chosen value is evaluated
+                let res = self.eval(cond, scope, buffer, reader)?;
+                match self.truth_of(&res) {
+                    Some(true) => return self.eval(then, scope, buffer, reader),
+                    Some(false) => return self.eval(other, scope, buffer, reader),
+                    None => {
+                        return Err(Error::new(
+                            ErrorType::RuntimeError,
+                            "Condition before ? is not boolean",
+                            None,
+                        ))
+                    }
+                }
             }
             Expr::Operation(op, args) => {
                 use Operator::*;
                 use RickrollObject::*;
                 if op.is_unary() && args.len() == 1 {
-                    let operand = self.eval(&args[0], scope)?;
+                    let operand = self.eval(&args[0], scope, buffer, reader)?;
                     return match op {
                         UnaryMinus => match operand {
                             Int(x) => Ok(Int(-x)),
@@ -67,15 +393,35 @@ impl Interpreter {
                             Bool(x) => Ok(Bool(!x)),
                             _ => Err(eval_err(op)),
                         },
+                        BitNot => match operand {
+                            Int(x) => Ok(Int(!x)),
+                            _ => Err(eval_err(op)),
+                        },
+                        CastInt => cast(operand, "INT"),
+                        CastFloat => cast(operand, "FLOAT"),
+                        CastChar => cast(operand, "CHAR"),
                         _ => panic!("Unary operator is not unary!"),
                     };
                 } else if !op.is_unary() && args.len() == 2 {
                     // expressions operands start from the top
-                    let first = self.eval(&args[1], scope)?;
-                    let second = self.eval(&args[0], scope)?;
+                    let first = self.eval(&args[1], scope, buffer, reader)?;
+                    let second = self.eval(&args[0], scope, buffer, reader)?;
+                    // arithmetic and ordering allow mixing Int and Float
+                    let (first, second) = match op {
+                        Add | Subtract | Multiply | Divide | Modulo | Power | Greater | Less
+                        | GreaterEquals | LessEquals => promote(first, second),
+                        _ => (first, second),
+                    };
                     let ans = match op {
                         ArrayAccess => match (first, second) {
-                            (Array(arr), Int(x)) => Ok(arr[x as usize].clone()),
+                            (Array(arr), Int(x)) => match resolve_index(x, arr.len()) {
+                                Some(ind) => Ok(arr[ind].clone()),
+                                None => Err(Error::new(
+                                    ErrorType::IndexOutOfBoundsError,
+                                    "Array index out of bounds",
+                                    None,
+                                )),
+                            },
                             _ => Err(eval_err(op)),
                         },
                         Add => match (first, second) {
@@ -98,21 +444,47 @@ impl Interpreter {
                                 if y == 0 {
                                     Err(Error::new(
                                         ErrorType::RuntimeError,
-                                        &format!("Division by zero")[..],
+                                        "Division by zero",
                                         None,
                                     ))
                                 } else {
                                     Ok(Int(x.wrapping_div(y)))
                                 }
                             },
+                            // floating point division by zero is infinity or NaN
                             (Float(x), Float(y)) => Ok(Float(x / y)),
                             _ => Err(eval_err(op)),
                         },
                         Modulo => match (first, second) {
-                            (Int(x), Int(y)) => Ok(Int(x.wrapping_rem(y))),
+                            (Int(x), Int(y)) => {
+                                if y == 0 {
+                                    Err(Error::new(
+                                        ErrorType::RuntimeError,
+                                        "Division by zero",
+                                        None,
+                                    ))
+                                } else {
+                                    Ok(Int(x.wrapping_rem(y)))
+                                }
+                            },
                             (Float(x), Float(y)) => Ok(Float(x % y)),
                             _ => Err(eval_err(op)),
                         },
+                        Power => match (first, second) {
+                            (Int(x), Int(y)) => {
+                                if y < 0 {
+                                    Err(Error::new(
+                                        ErrorType::RuntimeError,
+                                        "Negative integer exponent",
+                                        None,
+                                    ))
+                                } else {
+                                    Ok(Int(x.wrapping_pow(y as u32)))
+                                }
+                            },
+                            (Float(x), Float(y)) => Ok(Float(x.powf(y))),
+                            _ => Err(eval_err(op)),
+                        },
                         And => match (first, second) {
                             (Bool(x), Bool(y)) => Ok(Bool(x && y)),
                             _ => Err(eval_err(op)),
@@ -121,39 +493,63 @@ impl Interpreter {
                             (Bool(x), Bool(y)) => Ok(Bool(x || y)),
                             _ => Err(eval_err(op)),
                         },
+                        BitAnd => match (first, second) {
+                            (Int(x), Int(y)) => Ok(Int(x & y)),
+                            _ => Err(eval_err(op)),
+                        },
+                        BitOr => match (first, second) {
+                            (Int(x), Int(y)) => Ok(Int(x | y)),
+                            _ => Err(eval_err(op)),
+                        },
+                        BitXor => match (first, second) {
+                            (Int(x), Int(y)) => Ok(Int(x ^ y)),
+                            _ => Err(eval_err(op)),
+                        },
+                        ShiftLeft | ShiftRight => match (first, second) {
+                            (Int(x), Int(y)) => {
+                                if !(0..32).contains(&y) {
+                                    Err(Error::new(
+                                        ErrorType::RuntimeError,
+                                        "Shift amount out of range",
+                                        None,
+                                    ))
+                                } else if let ShiftLeft = op {
+                                    Ok(Int(x.wrapping_shl(y as u32)))
+                                } else {
+                                    Ok(Int(x.wrapping_shr(y as u32)))
+                                }
+                            },
+                            _ => Err(eval_err(op)),
+                        },
                         Greater => match (first, second) {
                             (Int(x), Int(y)) => Ok(Bool(x > y)),
                             (Float(x), Float(y)) => Ok(Bool(x > y)),
+                            (Array(x), Array(y)) => compare_arrays(&x, &y).map(|ord| Bool(ord == Ordering::Greater)),
                             _ => Err(eval_err(op)),
                         },
                         Less => match (first, second) {
                             (Int(x), Int(y)) => Ok(Bool(x < y)),
                             (Float(x), Float(y)) => Ok(Bool(x < y)),
+                            (Array(x), Array(y)) => compare_arrays(&x, &y).map(|ord| Bool(ord == Ordering::Less)),
                             _ => Err(eval_err(op)),
                         },
                         GreaterEquals => match (first, second) {
                             (Int(x), Int(y)) => Ok(Bool(x >= y)),
                             (Float(x), Float(y)) => Ok(Bool(x >= y)),
+                            (Array(x), Array(y)) => compare_arrays(&x, &y).map(|ord| Bool(ord != Ordering::Less)),
                             _ => Err(eval_err(op)),
                         },
                         LessEquals => match (first, second) {
                             (Int(x), Int(y)) => Ok(Bool(x <= y)),
                             (Float(x), Float(y)) => Ok(Bool(x <= y)),
+                            (Array(x), Array(y)) => compare_arrays(&x, &y).map(|ord| Bool(ord != Ordering::Greater)),
                             _ => Err(eval_err(op)),
                         },
-                        Equals => match (first, second) {
-                            (Int(x), Int(y)) => Ok(Bool(x == y)),
-                            (Float(x), Float(y)) => Ok(Bool(x == y)),
-                            (Bool(x), Bool(y)) => Ok(Bool(x == y)),
-                            (Char(x), Char(y)) => Ok(Bool(x == y)),
-                            _ => Ok(Bool(false)), // default false
-                        },
-                        NotEquals => match (first, second) {
-                            (Int(x), Int(y)) => Ok(Bool(x != y)),
-                            (Float(x), Float(y)) => Ok(Bool(x != y)),
-                            (Bool(x), Bool(y)) => Ok(Bool(x != y)),
-                            (Char(x), Char(y)) => Ok(Bool(x != y)),
-                            _ => Ok(Bool(true)), // default true
+                        Equals => Ok(Bool(first == second)),
+                        NotEquals => Ok(Bool(first != second)),
+                        In => match (first, second) {
+                            (x, Array(arr)) => Ok(Bool(arr.contains(&x))),
+                            _ => Err(eval_err(op)),
                         },
                         _ => panic!("Binary operator is not binary!"),
                     };
@@ -165,112 +561,415 @@ impl Interpreter {
         }
     }
 
+    // rebuilds an array with the element at a path of indices replaced
+    // every level is rebuilt with ArrayReplace semantics since arrays are immutable
+    fn replace_nested(
+        &mut self,
+        arr: RickrollObject,
+        indices: &[RickrollObject],
+        val: RickrollObject,
+        scope: &mut Scope,
+        buffer: &mut dyn Write,
+        reader: &mut dyn BufRead,
+    ) -> Result<RickrollObject, Error> {
+        let mut val = val;
+        if indices.len() > 1 {
+            // update the inner array first
+            let access = Expr::Operation(
+                Operator::ArrayAccess,
+                vec![Expr::Value(indices[0].clone()), Expr::Value(arr.clone())],
+            );
+            let inner = self.eval(&access, scope, buffer, reader)?;
+            val = self.replace_nested(inner, &indices[1..], val, scope, buffer, reader)?;
+        }
+        return (BUILTIN_FUNCTIONS["ArrayReplace"].function)(vec![arr, indices[0].clone(), val], buffer, reader);
+    }
+
     // execute a statement
-    // returns Ok(obj) if the function should return
+    // returns a signal telling the enclosing block how to continue
     pub fn execute(
         &mut self,
         statement: &ASTNode,
         scope: &mut Scope,
         buffer: &mut dyn Write,
         reader: &mut dyn BufRead,
-    ) -> Result<Option<RickrollObject>, Error> {
+    ) -> Result<Signal, Error> {
+        // guard against overflowing the native stack
+        if self.depth >= MAX_RECURSION_DEPTH {
+            return Err(Error::new(
+                ErrorType::StackOverflowError,
+                "Maximum recursion depth exceeded",
+                Some(statement.get_line()),
+            ));
+        }
+        self.check_interrupt(statement.get_line())?;
+        if let Some(hook) = &mut self.hook {
+            if !(hook.0)(statement.get_line(), scope, reader) {
+                return Err(Error::new(
+                    ErrorType::RuntimeError,
+                    "Execution stopped",
+                    Some(statement.get_line()),
+                ));
+            }
+        }
+        self.depth += 1;
+        self.trace_statement(statement.get_line());
+        let res = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, || {
+            self.execute_statement(statement, scope, buffer, reader)
+        });
+        self.depth -= 1;
+        return res;
+    }
+
+    fn execute_statement(
+        &mut self,
+        statement: &ASTNode,
+        scope: &mut Scope,
+        buffer: &mut dyn Write,
+        reader: &mut dyn BufRead,
+    ) -> Result<Signal, Error> {
         match statement {
             ASTNode::Say(ln, expr) => {
-                let res = self.wrap_check(self.eval(expr, scope), *ln)?;
-                writeln!(buffer, "{}", res).expect("Error when writing to buffer");
+                let res = wrap_check(self.eval(expr, scope, buffer, reader), *ln)?;
+                self.trace_value(&res);
+                if let (RickrollObject::Undefined, Some(out)) = (&res, &mut self.undefined_warnings) {
+                    writeln!(out.0, "Warning on line {}: printed value is UNDEFINED", ln)
+                        .expect("Error when writing warning");
+                }
+                // arrays of characters are printed as text
+                let text = match &res {
+                    RickrollObject::Array(arr)
+                        if !arr.is_empty()
+                            && arr.iter().all(|x| matches!(x, RickrollObject::Char(_))) =>
+                    {
+                        arr.iter().map(|x| x.to_string()).collect::<String>()
+                    }
+                    _ => res.to_string(),
+                };
+                writeln!(buffer, "{}", text).expect("Error when writing to buffer");
+                if let Some(printed) = &mut self.printed {
+                    printed.push((*ln, res));
+                }
             }
             ASTNode::Let(_, name) => {
                 scope.add_var(name.clone());
             }
-            ASTNode::Assign(ln, name, expr) => {
-                let res = self.wrap_check(self.eval(expr, scope), *ln)?;
-                scope.set_var(name.clone(), res);
+            ASTNode::Assign(ln, var, expr) => {
+                let res = wrap_check(self.eval(expr, scope, buffer, reader), *ln)?;
+                self.trace_value(&res);
+                if !scope.set(var, res) {
+                    return Err(Error::new(
+                        ErrorType::NameError,
+                        &format!("Variable {} doesn't exist", var.name)[..],
+                        Some(*ln),
+                    ));
+                }
+                wrap_check(self.check_memory(scope), *ln)?;
             }
-            ASTNode::While(ln, cond, body) => loop {
-                let res = self.wrap_check(self.eval(cond, scope), *ln)?;
-                match res {
-                    RickrollObject::Bool(x) => {
-                        if !x {
-                            break;
-                        }
+            ASTNode::Step(ln, var, step) => {
+                let res = match scope.get(var) {
+                    Some(RickrollObject::Int(x)) => RickrollObject::Int(x.wrapping_add(*step)),
+                    Some(RickrollObject::Float(x)) => RickrollObject::Float(x + *step as f32),
+                    None => {
+                        return Err(Error::new(
+                            ErrorType::NameError,
+                            &format!("Variable {} doesn't exist", var.name)[..],
+                            Some(*ln),
+                        ))
                     }
                     _ => {
                         return Err(Error::new(
                             ErrorType::RuntimeError,
-                            "While condition is not boolean",
+                            &format!("Variable {} is not a number", var.name)[..],
                             Some(*ln),
                         ))
                     }
+                };
+                self.trace_value(&res);
+                scope.set(var, res);
+            }
+            ASTNode::AssignIndex(ln, var, indices, expr) => {
+                let arr = match scope.get(var) {
+                    Some(arr) => arr,
+                    None => {
+                        return Err(Error::new(
+                            ErrorType::NameError,
+                            &format!("Variable {} doesn't exist", var.name)[..],
+                            Some(*ln),
+                        ))
+                    }
+                };
+                let mut idx_vals: Vec<RickrollObject> = Vec::new();
+                for index in indices {
+                    idx_vals.push(wrap_check(self.eval(index, scope, buffer, reader), *ln)?);
                 }
-                scope.push(Context::new());
-                for node in body {
-                    let res = self.execute(node, scope, buffer, reader)?;
-                    match res {
-                        Some(obj) => return Ok(Some(obj)),
-                        None => (),
+                let val = wrap_check(self.eval(expr, scope, buffer, reader), *ln)?;
+                let res = self.replace_nested(arr, &idx_vals, val, scope, buffer, reader);
+                let res = wrap_check(res, *ln)?;
+                wrap_check(self.check_value_size(&res), *ln)?;
+                self.trace_value(&res);
+                scope.set(var, res);
+                wrap_check(self.check_memory(scope), *ln)?;
+            }
+            ASTNode::While(ln, cond, body) => {
+                // one context is reused by every iteration of the body
+                let mut body_context = self.new_context();
+                loop {
+                    // loops are checked too, since their bodies may be empty
+                    self.check_interrupt(*ln)?;
+                    let res = wrap_check(self.eval(cond, scope, buffer, reader), *ln)?;
+                    match self.truth_of(&res) {
+                        Some(x) => {
+                            if !x {
+                                break;
+                            }
+                        }
+                        None => {
+                            return Err(Error::new(
+                                ErrorType::RuntimeError,
+                                "While condition is not boolean",
+                                Some(*ln),
+                            ))
+                        }
+                    }
+                    if let Some(signal) = self.execute_iteration(body, &mut body_context, scope, buffer, reader)? {
+                        return Ok(signal);
                     }
                 }
-                scope.pop();
-            },
-            ASTNode::If(ln, cond, body) => {
-                let res = self.wrap_check(self.eval(cond, scope), *ln)?;
-                match res {
-                    RickrollObject::Bool(x) => {
-                        if x {
-                            scope.push(Context::new());
-                            for node in body {
-                                let res = self.execute(node, scope, buffer, reader)?;
-                                match res {
-                                    Some(obj) => return Ok(Some(obj)),
-                                    None => (),
-                                }
+            }
+            ASTNode::DoWhile(ln, cond_ln, cond, body) => {
+                let mut body_context = self.new_context();
+                loop {
+                    self.check_interrupt(*ln)?;
+                    // the body runs before the condition is checked
+                    if let Some(signal) = self.execute_iteration(body, &mut body_context, scope, buffer, reader)? {
+                        return Ok(signal);
+                    }
+                    let res = wrap_check(self.eval(cond, scope, buffer, reader), *cond_ln)?;
+                    match self.truth_of(&res) {
+                        Some(x) => {
+                            if !x {
+                                break;
                             }
-                            scope.pop();
+                        }
+                        None => {
+                            return Err(Error::new(
+                                ErrorType::RuntimeError,
+                                "Do-while condition is not boolean",
+                                Some(*cond_ln),
+                            ))
                         }
                     }
+                }
+            }
+            ASTNode::For(ln, var, start, end, body) => {
+                let start = wrap_check(self.eval(start, scope, buffer, reader), *ln)?;
+                let end = wrap_check(self.eval(end, scope, buffer, reader), *ln)?;
+                let (start, end) = match (start, end) {
+                    (RickrollObject::Int(x), RickrollObject::Int(y)) => (x, y),
                     _ => {
                         return Err(Error::new(
                             ErrorType::RuntimeError,
-                            "While condition is not boolean",
+                            "For loop bounds are not integers",
+                            Some(*ln),
+                        ))
+                    }
+                };
+                // context holding the loop variable
+                scope.push(self.new_context());
+                scope.add_var(var.clone());
+                scope.set_var(var.clone(), RickrollObject::Int(start));
+                // the loop variable is the only one in the innermost context between iterations
+                let loop_var = Var {
+                    name: var.clone(),
+                    slot: Some(Slot { depth: 0, index: 0 }),
+                };
+                let mut body_context = self.new_context();
+                let signal = loop {
+                    self.check_interrupt(*ln)?;
+                    // the body may have assigned the loop variable
+                    if loop_counter(scope, &loop_var, *ln)? >= end {
+                        break Signal::Next;
+                    }
+                    if let Some(signal) = self.execute_iteration(body, &mut body_context, scope, buffer, reader)? {
+                        break signal;
+                    }
+                    let cur = loop_counter(scope, &loop_var, *ln)?;
+                    scope.set(&loop_var, RickrollObject::Int(cur.saturating_add(1)));
+                };
+                // leave the loop variable's context
+                scope.pop();
+                return Ok(signal);
+            }
+            ASTNode::If(ln, cond, body, else_body) => {
+                let res = wrap_check(self.eval(cond, scope, buffer, reader), *ln)?;
+                match self.truth_of(&res) {
+                    Some(x) => {
+                        let branch = if x { body } else { else_body };
+                        return self.execute_block(branch, scope, buffer, reader);
+                    }
+                    None => {
+                        return Err(Error::new(
+                            ErrorType::RuntimeError,
+                            "If condition is not boolean",
                             Some(*ln),
                         ))
                     }
                 }
             }
+            ASTNode::Match(ln, subject, cases, default) => {
+                let res = wrap_check(self.eval(subject, scope, buffer, reader), *ln)?;
+                // the first equal case is run, otherwise the default
+                let branch = match cases.iter().find(|(pattern, _)| *pattern == res) {
+                    Some((_, body)) => body,
+                    None => default,
+                };
+                return self.execute_block(branch, scope, buffer, reader);
+            }
             ASTNode::Run(ln, func, args) => {
                 let mut passed: Vec<RickrollObject> = Vec::new();
                 for arg in args {
-                    passed.push(scope.get_var(arg.clone()).unwrap());
+                    passed.push(wrap_check(self.eval(arg, scope, buffer, reader), *ln)?);
                 }
-                let tail = scope.behead();
-                scope.push(Context::new());
-                let res = self.run_function(func.clone(), passed, scope, buffer, reader);
-                self.wrap_check(res, *ln)?;
-                scope.behead();
-                scope.push_all(tail);
+                let res = self.call_function(func.clone(), passed, scope, buffer, reader);
+                wrap_check(res, *ln)?;
             }
-            ASTNode::RunAssign(ln, var, func, args) => {
+            ASTNode::RunAssign(ln, vars, func, args) => {
                 let mut passed: Vec<RickrollObject> = Vec::new();
                 for arg in args {
-                    passed.push(scope.get_var(arg.clone()).unwrap());
+                    passed.push(wrap_check(self.eval(arg, scope, buffer, reader), *ln)?);
+                }
+                let res = self.call_function(func.clone(), passed, scope, buffer, reader);
+                let res = wrap_check(res, *ln)?;
+                wrap_check(self.check_value_size(&res), *ln)?;
+                if vars.len() == 1 {
+                    scope.set_var(vars[0].clone(), res);
+                } else {
+                    // several variables unpack an array of the same length
+                    match res {
+                        RickrollObject::Array(arr) if arr.len() == vars.len() => {
+                            for (var, val) in vars.iter().zip(arr.iter()) {
+                                scope.set_var(var.clone(), val.clone());
+                            }
+                        }
+                        _ => {
+                            return Err(Error::new(
+                                ErrorType::RuntimeError,
+                                &format!("Cannot unpack {} into {} variables", res, vars.len())[..],
+                                Some(*ln),
+                            ))
+                        }
+                    }
                 }
-                let tail = scope.behead();
-                scope.push(Context::new());
-                let res = self.run_function(func.clone(), passed, scope, buffer, reader);
-                let res = self.wrap_check(res, *ln)?;
-                scope.behead();
-                scope.push_all(tail);
-                scope.set_var(var.clone(), res);
+                wrap_check(self.check_memory(scope), *ln)?;
             },
-            ASTNode::Return(ln, expr) => {
-                let res = self.wrap_check(self.eval(expr, scope), *ln)?;
-                return Ok(Some(res));
+            ASTNode::Return(ln, exprs) => {
+                let mut values: Vec<RickrollObject> = Vec::new();
+                for expr in exprs {
+                    values.push(wrap_check(self.eval(expr, scope, buffer, reader), *ln)?);
+                }
+                // several values are returned as an array
+                let res = if values.len() == 1 {
+                    values.pop().unwrap()
+                } else {
+                    RickrollObject::Array(Rc::new(values))
+                };
+                wrap_check(self.check_value_size(&res), *ln)?;
+                return Ok(Signal::Return(res));
+            },
+            ASTNode::Break(_, label) => {
+                return Ok(Signal::Break(label.clone()));
+            },
+            ASTNode::Labelled(_, label, node) => {
+                match self.execute_statement(node, scope, buffer, reader)? {
+                    // the loop was left by a break to its label
+                    Signal::Break(Some(target)) if target == *label => (),
+                    signal => return Ok(signal),
+                }
             },
             _ => {
                 panic!("Interpreter::execute called with Function");
             },
         }
-        return Ok(None);
+        return Ok(Signal::Next);
+    }
+
+    // executes the statements of a branch or loop body in a fresh context
+    // stops at the first return or break and passes it on
+    fn execute_block(
+        &mut self,
+        branch: &[ASTNode],
+        scope: &mut Scope,
+        buffer: &mut dyn Write,
+        reader: &mut dyn BufRead,
+    ) -> Result<Signal, Error> {
+        scope.push(self.new_context());
+        let signal = self.execute_statements(branch, scope, buffer, reader)?;
+        // leave the branch's context first
+        scope.pop();
+        return Ok(signal);
+    }
+
+    // runs the body of a loop once, in a context that is reused by every iteration
+    // returns the signal that ends the loop, or None if it goes on
+    fn execute_iteration(
+        &mut self,
+        body: &[ASTNode],
+        context: &mut Context,
+        scope: &mut Scope,
+        buffer: &mut dyn Write,
+        reader: &mut dyn BufRead,
+    ) -> Result<Option<Signal>, Error> {
+        let mut reused = std::mem::take(context);
+        reused.clear();
+        scope.push(reused);
+        let signal = self.execute_statements(body, scope, buffer, reader)?;
+        *context = scope.pop();
+        match signal {
+            Signal::Next => return Ok(None),
+            // a break leaves this loop and the statement after it runs
+            Signal::Break(None) => return Ok(Some(Signal::Next)),
+            // a return or a break to an outer loop
+            signal => return Ok(Some(signal)),
+        }
+    }
+
+    // executes statements in the innermost context, stopping at the first return or break
+    fn execute_statements(
+        &mut self,
+        branch: &[ASTNode],
+        scope: &mut Scope,
+        buffer: &mut dyn Write,
+        reader: &mut dyn BufRead,
+    ) -> Result<Signal, Error> {
+        for node in branch {
+            match self.execute(node, scope, buffer, reader)? {
+                Signal::Next => (),
+                signal => return Ok(signal),
+            }
+        }
+        return Ok(Signal::Next);
+    }
+
+    // calls a function from a block, so that it only sees the global context
+    fn call_function(
+        &mut self,
+        func: String,
+        passed: Vec<RickrollObject>,
+        scope: &mut Scope,
+        buffer: &mut dyn Write,
+        reader: &mut dyn BufRead,
+    ) -> Result<RickrollObject, Error> {
+        // statements of an interactive session run in the global context itself
+        let tail = if scope.len() > 1 { scope.behead() } else { Vec::new() };
+        let tail_size: usize = tail.iter().map(|x| x.size()).sum();
+        self.suspended_memory += tail_size;
+        scope.push(self.new_context());
+        let res = self.run_function(func, passed, scope, buffer, reader);
+        self.suspended_memory -= tail_size;
+        scope.behead();
+        scope.push_all(tail);
+        return res;
     }
 
     // executes a function
@@ -282,28 +981,72 @@ impl Interpreter {
         buffer: &mut dyn Write,
         reader: &mut dyn BufRead,
     ) -> Result<RickrollObject, Error> {
+        if !self.functions.contains_key(&func) {
+            if let Some(builtin) = self.builtins.get(&func) {
+                let res = (builtin.function)(passed, buffer, reader)?;
+                self.check_value_size(&res)?;
+                return Ok(res);
+            }
+        }
         if !self.functions.contains_key(&func) && BUILTIN_FUNCTIONS.contains_key(&func) {
+            if func == "Sleep" && !self.allow_sleep {
+                return Err(Error::new(
+                    ErrorType::RuntimeError,
+                    "Sleep is disabled",
+                    None,
+                ));
+            }
+            // check the size of a filled array before allocating it
+            if func == "ArrayFill" {
+                if let Some(RickrollObject::Int(count)) = passed.get(1) {
+                    self.check_collection_size((*count).max(0) as usize)?;
+                }
+            }
             let mut arg_vals = Vec::new();
             for arg in passed {
                 arg_vals.push(arg.clone());
             }
-            return BUILTIN_FUNCTIONS[&func](arg_vals, buffer, reader);
+            let builtin = if func == "ReadLine" && self.lossy_input {
+                read_line_lossy as LibFunction
+            } else {
+                BUILTIN_FUNCTIONS[&func].function
+            };
+            let res = builtin(arg_vals, buffer, reader)?;
+            self.check_value_size(&res)?;
+            return Ok(res);
         }
-        let function = self.functions.get(&func).unwrap().clone();
-        match function {
-            ASTNode::Function(_, _, args, body) => {
+        let function = Rc::clone(&self.functions[&func]);
+        match &*function {
+            ASTNode::Function(_, _, args, defaults, body) => {
+                // the last parameters may be left out if they have defaults
+                let required = args.len() - defaults.len();
+                if passed.len() < required || passed.len() > args.len() {
+                    return Err(Error::new(
+                        ErrorType::RuntimeError,
+                        &format!("Wrong number of arguments for {}", func)[..],
+                        None,
+                    ));
+                }
                 // function arguments
-                for (arg, val) in args.iter().zip(passed.iter()) {
+                for (ind, arg) in args.iter().enumerate() {
+                    // defaults are evaluated in the function, after the earlier arguments
+                    let val = match passed.get(ind) {
+                        Some(val) => val.clone(),
+                        None => self.eval(&defaults[ind - required], scope, buffer, reader)?,
+                    };
                     scope.add_var(arg.clone());
-                    scope.set_var(arg.clone(), val.clone());
+                    scope.set_var(arg.clone(), val);
                 }
+                self.check_memory(scope)?;
                 for node in body {
-                    let res = self.execute(&node, scope, buffer, reader)?;
+                    let res = self.execute(node, scope, buffer, reader)?;
                     match res {
-                        Some(obj) => { 
+                        Signal::Return(obj) => { 
                             return Ok(obj);
                         },
-                        None => (),
+                        // the parser only allows breaks inside of loops
+                        Signal::Break(_) => panic!("Interpreter::run_function called with break outside of loop"),
+                        Signal::Next => (),
                     }
                 }
                 return Ok(RickrollObject::Undefined);
@@ -318,16 +1061,111 @@ impl Interpreter {
         buffer: &mut dyn Write,
         reader: &mut dyn BufRead,
     ) -> Result<RickrollObject, Error> {
-        let mut global_scope = Scope::new();
+        let mut global_scope = Scope::from_vec(vec![self.new_context()]);
+        self.suspended_memory = 0;
+        if let Some(printed) = &mut self.printed {
+            printed.clear();
+        }
+        self.exit_code = None;
+        let res = self.run_blocks(&mut global_scope, buffer, reader);
+        let res = self.stop_on_exit(res, RickrollObject::Undefined);
+        // keep only the global context, even if the program failed part way
+        self.globals = Scope::from_vec(vec![global_scope.get_global().clone()]);
+        return res;
+    }
+
+    // turns a call to Exit into a successful end of the program, with the given result
+    fn stop_on_exit<T>(&mut self, res: Result<T, Error>, stopped: T) -> Result<T, Error> {
+        if let Err(err) = &res {
+            if let Some(code) = err.exit_code() {
+                self.exit_code = Some(code);
+                return Ok(stopped);
+            }
+        }
+        return res;
+    }
+
+    // status passed to Exit during the last run or input, None if it wasn't called
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    // executes the program with the given standard input
+    // returns everything it printed along with its result
+    pub fn run_to_string(&mut self, stdin: &str) -> Result<(String, RickrollObject), Error> {
+        let mut output: Vec<u8> = Vec::new();
+        let res = self.run(&mut output, &mut BufReader::new(stdin.as_bytes()))?;
+        return Ok((String::from_utf8_lossy(&output).to_string(), res));
+    }
+
+    // runs one input of an interactive session, as parsed by Parser::parse_input
+    // the statements see the global variables left by the earlier inputs
+    pub fn run_input(
+        &mut self,
+        functions: Vec<ASTNode>,
+        statements: &[ASTNode],
+        buffer: &mut dyn Write,
+        reader: &mut dyn BufRead,
+    ) -> Result<(), Error> {
+        for function in functions {
+            if let ASTNode::Function(_, name, _, _, _) = &function {
+                self.functions.insert(name.clone(), Rc::new(function));
+            }
+        }
+        self.exit_code = None;
+        let mut global_scope = std::mem::take(&mut self.globals);
+        let mut res = Ok(());
+        for (ind, node) in statements.iter().enumerate() {
+            match self.execute(node, &mut global_scope, buffer, reader) {
+                Ok(Signal::Next) => continue,
+                // like in an intro block, a return ends the input
+                Ok(Signal::Return(_)) => (),
+                Ok(Signal::Break(_)) => panic!("Interpreter::run_input called with break outside of loop"),
+                Err(err) => {
+                    // the contexts of the blocks the error left are dropped
+                    if global_scope.len() > 1 {
+                        global_scope.behead();
+                    }
+                    res = Err(err);
+                }
+            }
+            // the parser gave slots to the globals of the skipped statements, so they are declared
+            for node in &statements[(ind + 1)..] {
+                match node {
+                    ASTNode::Let(_, name) if !global_scope.get_global().has_var(name.clone()) => {
+                        global_scope.add_var(name.clone());
+                    }
+                    _ => (),
+                }
+            }
+            break;
+        }
+        // variables assigned before an error are kept
+        self.globals = global_scope;
+        return self.stop_on_exit(res, ());
+    }
+
+    // global variables left by the last call to run
+    pub fn last_globals(&self) -> &Scope {
+        &self.globals
+    }
+
+    // executes the intro and chorus blocks
+    fn run_blocks(
+        &mut self,
+        global_scope: &mut Scope,
+        buffer: &mut dyn Write,
+        reader: &mut dyn BufRead,
+    ) -> Result<RickrollObject, Error> {
         if self.functions.contains_key(&String::from("[INTRO]")) {
-            self.run_function(String::from("[INTRO]"), Vec::new(), &mut global_scope, buffer, reader)?;
+            self.run_function(String::from("[INTRO]"), Vec::new(), global_scope, buffer, reader)?;
         }
         if self.functions.contains_key(&String::from("[CHORUS]")) {
-            global_scope.push(Context::new());
+            global_scope.push(self.new_context());
             let val = self.run_function(
                 String::from("[CHORUS]"),
                 Vec::new(),
-                &mut global_scope,
+                global_scope,
                 buffer,
                 reader,
             );
@@ -10,7 +10,7 @@ pub enum Token {
     Punc(usize, String),
     Name(usize, String),
     Value(usize, RickrollObject),
-    Operator(usize, String),
+    Operator(usize, Operator),
     Statement(usize, String),
 }
 
@@ -27,39 +27,256 @@ impl Token {
     }
 }
 
+// aliases defined by "You know the rules: NAME means TEXT", as (NAME, TEXT)
+pub type Aliases = Vec<(String, String)>;
+
+// statements of the language, whose words can't be used as alias names
+const LYRICS: &[&str] = &[
+    "Never gonna say goodbye to",
+    "Never gonna let down",
+    "Never gonna give",
+    "Never gonna raise lower multiply divide by",
+    "Never gonna turn up down",
+    "Inside we both know",
+    "We've known each other for from to",
+    "We know the game and we're gonna play it",
+    "Don't tell me you're too blind to see",
+    "I just wanna tell you how I'm feeling",
+    "Gotta make you understand",
+    "A full commitment's what I'm thinking of",
+    "You wouldn't get this from any other guy",
+    "Your heart's been aching but you're too shy to say it",
+    "Chorus Intro Verse",
+    "Never gonna run and desert",
+    "(Ooh give you up) Never gonna run",
+    "(Ooh) Never gonna give, never gonna give (give you)",
+    "You know the rules: means",
+    "We're no strangers to love",
+];
+
+// whether a word is part of a statement or a keyword of expressions
+fn is_keyword(word: &str) -> bool {
+    let in_lyrics = LYRICS
+        .iter()
+        .any(|lyric| lyric.split(|x: char| !x.is_ascii_alphanumeric() && x != '_').any(|x| x == word));
+    return in_lyrics
+        || from_word_operator(word).is_some()
+        || from_constant(word).is_some()
+        || ["INT", "FLOAT", "CHAR"].contains(&word);
+}
+
 #[derive(Debug)]
 pub struct Lexer {
     ptr: usize,
     raw: Vec<String>,
     lexed: Vec<Token>,
+    aliases: Aliases, // aliases defined before the current line
+}
+
+// splits source code into lines, which end with "\n", "\r\n" or a lone "\r"
+pub fn split_lines(raw_text: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    let mut cur = String::new();
+    let mut chars = raw_text.chars().peekable();
+    while let Some(chr) = chars.next() {
+        if chr == '\n' || chr == '\r' {
+            if chr == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            res.push(cur);
+            cur = String::new();
+        } else {
+            cur.push(chr);
+        }
+    }
+    res.push(cur);
+    return res;
 }
 
 impl Lexer {
     pub fn new(raw_text: String) -> Lexer {
         Lexer {
             ptr: 0,
-            raw: {
-                let mut res = Vec::new();
-                let mut cur = String::new();
-                for chr in raw_text.chars() {
-                    if chr == '\n' {
-                        res.push(cur);
-                        cur = String::new();
-                    } else {
-                        cur.push(chr);
-                    }
-                }
-                res.push(cur);
-                res
-            },
+            raw: split_lines(&raw_text),
             lexed: Vec::new(),
+            aliases: Vec::new(),
         }
     }
 
+    // starts with aliases defined earlier, like by the inputs of an interactive session
+    pub fn with_aliases(mut self, aliases: Aliases) -> Lexer {
+        self.aliases = aliases;
+        self
+    }
+
     fn has_more(&self) -> bool {
         self.ptr < self.raw.len()
     }
 
+    // comments start with "#" or "We're no strangers to love"
+    fn is_comment(line: &str) -> bool {
+        line.starts_with('#') || line.starts_with("We're no strangers to love")
+    }
+
+    // finds a pattern in a line, skipping over string and character literals
+    fn find_unquoted(line: &str, pattern: &str) -> Option<usize> {
+        let mut quote: Option<char> = None;
+        let mut escaped = false;
+        for (ind, chr) in line.char_indices() {
+            match quote {
+                Some(q) => {
+                    if escaped {
+                        escaped = false;
+                    } else if chr == '\\' {
+                        escaped = true;
+                    } else if chr == q {
+                        quote = None;
+                    }
+                }
+                None => {
+                    if chr == '"' || chr == '\'' {
+                        quote = Some(chr);
+                    } else if line[ind..].starts_with(pattern) {
+                        return Some(ind);
+                    }
+                }
+            }
+        }
+        return None;
+    }
+
+    // replaces every whole word equal to name with text, except in literals
+    // an apostrophe that doesn't close a character literal is just a letter of the lyrics
+    fn replace_word(line: &str, name: &str, text: &str) -> String {
+        let chars: Vec<char> = line.chars().collect();
+        let mut res = String::new();
+        let mut ind = 0;
+        while ind < chars.len() {
+            let chr = chars[ind];
+            if chr == '"' {
+                // copy the string literal up to its closing quote
+                res.push(chr);
+                ind += 1;
+                while ind < chars.len() {
+                    res.push(chars[ind]);
+                    if chars[ind] == '\\' && ind + 1 < chars.len() {
+                        res.push(chars[ind + 1]);
+                        ind += 1;
+                    } else if chars[ind] == '"' {
+                        break;
+                    }
+                    ind += 1;
+                }
+                ind += 1;
+            } else if chr == '\'' && (chars.get(ind + 1) == Some(&'\\') || chars.get(ind + 2) == Some(&'\'')) {
+                // copy the character literal, which may have an escaped quote
+                let mut end = if chars[ind + 1] == '\\' { ind + 3 } else { ind + 2 };
+                while end < chars.len() && chars[end] != '\'' {
+                    end += 1;
+                }
+                let end = end.min(chars.len() - 1);
+                res.extend(&chars[ind..=end]);
+                ind = end + 1;
+            } else if chr.is_ascii_alphanumeric() || chr == '_' {
+                let start = ind;
+                while ind < chars.len() && (chars[ind].is_ascii_alphanumeric() || chars[ind] == '_') {
+                    ind += 1;
+                }
+                let word: String = chars[start..ind].iter().collect();
+                res += if word == name { text } else { &word };
+            } else {
+                res.push(chr);
+                ind += 1;
+            }
+        }
+        return res;
+    }
+
+    // defines an alias from "You know the rules: NAME means TEXT"
+    // the definition is skipped like a blank line, so the lines keep their numbers
+    fn define_alias(&mut self, line: &str) -> Result<(), Error> {
+        let slice = &line[20..]; // [A-Za-z_]\\w* means .+
+        let index = slice.find(' ').unwrap();
+        let name = String::from(&slice[..index]);
+        if is_keyword(&name) {
+            return Err(Error::new(
+                ErrorType::NameError,
+                &format!("Alias {} is a keyword", name)[..],
+                Some(self.ptr + 1),
+            ));
+        }
+        if self.aliases.iter().any(|(other, _)| *other == name) {
+            return Err(Error::new(
+                ErrorType::NameError,
+                &format!("Alias {} already exists", name)[..],
+                Some(self.ptr + 1),
+            ));
+        }
+        // earlier aliases can be used in the text
+        let mut text = String::from(&slice[(index + 7)..]);
+        for (name, expanded) in self.aliases.iter() {
+            text = Lexer::replace_word(&text, name, expanded);
+        }
+        self.aliases.push((name, text));
+        return Ok(());
+    }
+
+    // expands an alias at the start of a line, which stands for the start of a statement
+    fn expand_statement(&self, line: &str) -> String {
+        let end = line
+            .find(|x: char| !x.is_ascii_alphanumeric() && x != '_')
+            .unwrap_or(line.len());
+        for (name, text) in self.aliases.iter() {
+            if line[..end] == name[..] {
+                return format!("{}{}", text, &line[end..]);
+            }
+        }
+        return String::from(line);
+    }
+
+    // lexes an expression of the current line, after expanding the aliases in it
+    fn lex_expr(&self, expr: String) -> Result<Vec<Token>, Error> {
+        let mut expr = expr;
+        for (name, text) in self.aliases.iter() {
+            expr = Lexer::replace_word(&expr, name, text);
+        }
+        return self.wrap_check(ExprLexer::new(expr, self.ptr + 1).make_tokens());
+    }
+
+    // splits a line at every comma outside of literals and parentheses
+    fn split_unquoted(line: &str) -> Vec<&str> {
+        let mut segments: Vec<&str> = Vec::new();
+        let mut quote: Option<char> = None;
+        let mut escaped = false;
+        let mut depth = 0;
+        let mut start = 0;
+        for (ind, chr) in line.char_indices() {
+            match quote {
+                Some(q) => {
+                    if escaped {
+                        escaped = false;
+                    } else if chr == '\\' {
+                        escaped = true;
+                    } else if chr == q {
+                        quote = None;
+                    }
+                }
+                None => match chr {
+                    '"' | '\'' => quote = Some(chr),
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    ',' if depth == 0 => {
+                        segments.push(&line[start..ind]);
+                        start = ind + 1;
+                    }
+                    _ => (),
+                },
+            }
+        }
+        segments.push(&line[start..]);
+        return segments;
+    }
+
     // wraps a traceback around a possible error
     fn wrap_check<T>(&self, res: Result<T, Error>) -> Result<T, Error> {
         if let Err(error) = res {
@@ -68,98 +285,289 @@ impl Lexer {
         return res;
     }
 
+    // drops the empty segment after a single trailing comma ("A, B,") of a split list
+    fn without_trailing_comma(mut segments: Vec<&str>) -> Vec<&str> {
+        if segments.len() > 1 && segments.last().is_some_and(|x| x.trim().is_empty()) {
+            segments.pop();
+        }
+        return segments;
+    }
+
+    // error for the blank name of a list, at a position counting from 1
+    fn blank_name(&self, position: usize) -> Error {
+        return Error::new(
+            ErrorType::NameError,
+            &(format!("Blank variable name at position {}", position))[..],
+            Some(self.ptr + 1),
+        );
+    }
+
     // helper function splitting a string of the form "A, BCD, EEE" into ["A", "BCD", "EEE"]
+    // a single trailing comma ("A, B,") is allowed
     fn split_vars(&self, raw: String, empty: String) -> Result<Vec<String>, Error> {
         let mut args: Vec<String> = Vec::new();
-        let mut cur: String = String::new();
-        for chr in String::from(raw.trim()).chars() {
-            // valid character
-            if chr.is_ascii_alphabetic() || chr == '_' {
-                cur.push(chr);
-            } else if chr == ',' {
-                // variable break
-                if cur.is_empty() {
+        // blank or legacy keyword returns no arguments
+        if raw.trim().is_empty() || raw.trim() == empty {
+            return Ok(args);
+        }
+        let segments = Lexer::without_trailing_comma(raw.trim().split(',').collect());
+        for (ind, segment) in segments.iter().enumerate() {
+            let mut cur: String = String::new();
+            for chr in segment.chars() {
+                // valid character, digits can't start a name
+                if chr.is_ascii_alphabetic() || chr == '_' || (chr.is_ascii_digit() && !cur.is_empty()) {
+                    cur.push(chr);
+                } else if !chr.is_ascii_whitespace() {
+                    // illegal character
                     return Err(Error::new(
-                        ErrorType::NameError,
-                        "Blank variable name",
+                        ErrorType::IllegalArgumentError,
+                        &(format!("Illegal character \"{}\" in variable", chr))[..],
                         Some(self.ptr + 1),
                     ));
                 }
-                args.push(cur.to_owned());
-                cur.clear();
-            } else if !chr.is_ascii_whitespace() {
-                // illegal character
+            }
+            if cur.is_empty() {
+                return Err(self.blank_name(ind + 1));
+            }
+            args.push(cur);
+        }
+        return Ok(args);
+    }
+
+    // lexes the parameters of a function, which may have defaults (NAME = EXPR)
+    // parameters are separated by "," tokens and defaults start with "="
+    fn split_params(&self, raw: String, empty: String) -> Result<Vec<Token>, Error> {
+        let mut tokens: Vec<Token> = Vec::new();
+        // blank or legacy keyword returns no parameters
+        if raw.trim().is_empty() || raw.trim() == empty {
+            return Ok(tokens);
+        }
+        let segments = Lexer::without_trailing_comma(Lexer::split_unquoted(raw.trim()));
+        for (ind, segment) in segments.iter().enumerate() {
+            let (name, default) = match Lexer::find_unquoted(segment, "=") {
+                Some(pos) => (&segment[..pos], Some(&segment[(pos + 1)..])),
+                None => (*segment, None),
+            };
+            let name = self.split_vars(String::from(name), String::new())?;
+            if name.is_empty() {
+                return Err(self.blank_name(ind + 1));
+            }
+            if ind > 0 {
+                tokens.push(Token::Punc(self.ptr + 1, String::from(",")));
+            }
+            tokens.push(Token::Name(self.ptr + 1, name[0].clone()));
+            if let Some(default) = default {
+                tokens.push(Token::Punc(self.ptr + 1, String::from("=")));
+                let expr = self.lex_expr(String::from(default))?;
+                tokens.extend(expr);
+            }
+        }
+        return Ok(tokens);
+    }
+
+    // lexes the comma separated expressions passed to a function
+    // arguments are separated by "," tokens
+    fn split_args(&self, raw: String, empty: String) -> Result<Vec<Token>, Error> {
+        let mut tokens: Vec<Token> = Vec::new();
+        // blank or legacy keyword returns no arguments
+        if raw.trim().is_empty() || raw.trim() == empty {
+            return Ok(tokens);
+        }
+        let segments = Lexer::without_trailing_comma(Lexer::split_unquoted(raw.trim()));
+        for (ind, segment) in segments.iter().enumerate() {
+            if segment.trim().is_empty() {
+                // ignore the empty segment after a trailing comma
+                if ind == segments.len() - 1 && ind > 0 {
+                    break;
+                }
                 return Err(Error::new(
-                    ErrorType::IllegalArgumentError,
-                    &(format!("Illegal character \"{}\" in variable", chr))[..],
+                    ErrorType::SyntaxError,
+                    &(format!("Blank argument at position {}", ind + 1))[..],
                     Some(self.ptr + 1),
                 ));
             }
+            if ind > 0 {
+                tokens.push(Token::Punc(self.ptr + 1, String::from(",")));
+            }
+            let arg = self.lex_expr(String::from(*segment))?;
+            tokens.extend(arg);
         }
-        // empty returns no arguments
-        if cur == empty {
-            return Ok(Vec::new());
-        }
-        if !cur.is_empty() {
-            args.push(cur.to_owned());
-        }
-        return Ok(args);
+        return Ok(tokens);
+    }
+
+    pub fn parse(self) -> Result<Vec<Token>, Error> {
+        let (tokens, _) = self.parse_with_aliases()?;
+        return Ok(tokens);
     }
 
-    pub fn parse(mut self) -> Result<Vec<Token>, Error> {
+    // like parse, also returning the aliases defined by the end of the source
+    pub fn parse_with_aliases(mut self) -> Result<(Vec<Token>, Aliases), Error> {
         // regexes for matching statements
         lazy_static! {
+            // an alias for the text after "means"
+            static ref ALIAS: Regex = Regex::new("^You know the rules: [A-Za-z_]\\w* means .+$").unwrap();
+            // break out of loop, or out of the loop with a label
+            static ref BREAK: Regex = Regex::new("^Never gonna say goodbye( to [A-Za-z_]\\w*)?$").unwrap();
+            static ref LABEL: Regex = Regex::new("^\\(Ooh [A-Za-z_]\\w*\\) .+$").unwrap();
             // print
             static ref SAY: Regex = Regex::new("^Never gonna say .+$").unwrap();
             // let + assign to var
-            static ref LET: Regex = Regex::new("^Never gonna let \\w+ down$").unwrap();
-            static ref ASSIGN: Regex = Regex::new("^Never gonna give \\w+ .+$").unwrap();
+            static ref LET: Regex = Regex::new("^Never gonna let [A-Za-z_]\\w* down$").unwrap();
+            static ref ASSIGN: Regex = Regex::new("^Never gonna give [A-Za-z_]\\w* .+$").unwrap();
+            // update a variable with an operation, like "+="
+            static ref COMPOUND: Regex = Regex::new("^Never gonna (raise|lower|multiply|divide) [A-Za-z_]\\w* by .+$").unwrap();
+            // add or subtract one
+            static ref STEP: Regex = Regex::new("^Never gonna turn [A-Za-z_]\\w* (up|down)$").unwrap();
+            static ref ASSIGN_INDEX: Regex = Regex::new("^Never gonna give [A-Za-z_]\\w*(:(\\w+|~\\d+))+ .+$").unwrap();
             // check, if, and while
             static ref CHECK: Regex = Regex::new("^Inside we both know .+$").unwrap();
+            static ref FOR: Regex = Regex::new("^We\'ve known each other for [A-Za-z_]\\w* from .+ to .+$").unwrap();
             static ref WHILE_END: Regex = Regex::new("^We know the game and we\'re gonna play it$").unwrap();
+            static ref ELSE: Regex = Regex::new("^Don\'t tell me you\'re too blind to see$").unwrap();
+            // do-while loop, with the condition at the end
+            static ref DO: Regex = Regex::new("^I just wanna tell you how I\'m feeling$").unwrap();
+            static ref DO_END: Regex = Regex::new("^Gotta make you understand .+$").unwrap();
+            // match and its cases, which also end with IF_END
+            static ref MATCH: Regex = Regex::new("^A full commitment\'s what I\'m thinking of .+$").unwrap();
+            static ref DEFAULT: Regex = Regex::new("^You wouldn\'t get this from any other guy$").unwrap();
+            static ref CASE: Regex = Regex::new("^You wouldn\'t get this from .+$").unwrap();
             static ref IF_END: Regex = Regex::new("^Your heart\'s been aching but you\'re too shy to say it$").unwrap();
             // blocks (functions)
             static ref CHORUS: Regex = Regex::new("^\\[Chorus\\]$").unwrap();
             static ref INTRO: Regex = Regex::new("^\\[Intro\\]$").unwrap();
-            static ref VERSE: Regex = Regex::new("^\\[Verse \\w+\\]$").unwrap();
+            static ref VERSE: Regex = Regex::new("^\\[Verse [A-Za-z_]\\w*\\]$").unwrap();
             // function statements
-            static ref RUN: Regex = Regex::new("^Never gonna run \\w+ and desert .+$").unwrap();
-            static ref RUN_ASSIGN: Regex = Regex::new("^\\(Ooh give you \\w+\\) Never gonna run \\w+ and desert .+$").unwrap();
+            static ref RUN: Regex = Regex::new("^Never gonna run [A-Za-z_]\\w* and desert(\\s.*)?$").unwrap();
+            static ref RUN_ASSIGN: Regex = Regex::new("^\\(Ooh give you [A-Za-z_]\\w*(\\s*,\\s*[A-Za-z_]\\w*)*\\) Never gonna run [A-Za-z_]\\w* and desert(\\s.*)?$").unwrap();
             static ref RETURN: Regex = Regex::new("^\\(Ooh\\) Never gonna give, never gonna give \\(give you .+\\)$").unwrap();
             // function parameters
-            static ref ARGS: Regex = Regex::new("\\(Ooh give you .+\\)").unwrap();
+            static ref ARGS: Regex = Regex::new("^\\(Ooh give you(\\s.*)?\\)$").unwrap();
         }
         // iterate over raw
         while self.has_more() {
-            // try to match a statement
-            let curln = self.raw[self.ptr].trim();
-            if curln == "" {
+            // skip blank lines and comments
+            let rawln = self.raw[self.ptr].trim();
+            if rawln.is_empty() || Lexer::is_comment(rawln) {
                 self.ptr += 1;
                 continue;
+            }
+            if ALIAS.is_match(rawln) {
+                let rawln = String::from(rawln);
+                self.define_alias(&rawln)?;
+                self.ptr += 1;
+                continue;
+            }
+            // try to match a statement
+            let line = self.expand_statement(rawln);
+            let curln = line.trim();
+            if BREAK.is_match(curln) {
+                // ^Never gonna say goodbye( to [A-Za-z_]\\w*)?$
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from("BREAK")));
+                if curln.len() > 23 {
+                    self.lexed
+                        .push(Token::Name(self.ptr + 1, String::from(&curln[27..])));
+                }
+            } else if LABEL.is_match(curln) {
+                // ^\\(Ooh [A-Za-z_]\\w*\\) .+$
+                let ind = curln.find(')').unwrap();
+                let label = String::from(&curln[5..ind]);
+                let rest = String::from(&curln[(ind + 1)..]);
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from("LABEL")));
+                self.lexed.push(Token::Name(self.ptr + 1, label));
+                // the labelled statement is lexed from the rest of the line
+                self.raw[self.ptr] = rest;
+                continue;
             } else if SAY.is_match(curln) {
                 // ^Never gonna say .+$
                 let expr = String::from(&curln[16..]);
-                let tokens = self.wrap_check(ExprLexer::new(expr, self.ptr + 1).make_tokens())?;
+                let tokens = self.lex_expr(expr)?;
                 self.lexed
                     .push(Token::Statement(self.ptr + 1, String::from("SAY")));
                 for token in tokens {
                     self.lexed.push(token);
                 }
             } else if LET.is_match(curln) {
-                // ^Never gonna let \\w+ down$
+                // ^Never gonna let [A-Za-z_]\\w* down$
                 let varname = String::from(&curln[16..(curln.len() - 5)]);
                 self.lexed
                     .push(Token::Statement(self.ptr + 1, String::from("LET")));
                 self.lexed.push(Token::Name(self.ptr + 1, varname));
+            } else if COMPOUND.is_match(curln) {
+                // ^Never gonna (raise|lower|multiply|divide) [A-Za-z_]\\w* by .+$
+                let slice = &curln[12..]; // (raise|lower|multiply|divide) [A-Za-z_]\\w* by .+
+                let index = slice.find(' ').unwrap();
+                let op = match &slice[..index] {
+                    "raise" => Operator::Add,
+                    "lower" => Operator::Subtract,
+                    "multiply" => Operator::Multiply,
+                    _ => Operator::Divide,
+                };
+                let slice = &slice[(index + 1)..]; // [A-Za-z_]\\w* by .+
+                let index = slice.find(' ').unwrap();
+                let varname = String::from(&slice[..index]);
+                let expr = String::from(&slice[(index + 4)..]);
+                let tokens = self.lex_expr(expr)?;
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from("COMPOUND")));
+                self.lexed.push(Token::Name(self.ptr + 1, varname));
+                self.lexed.push(Token::Operator(self.ptr + 1, op));
+                for token in tokens {
+                    self.lexed.push(token);
+                }
+            } else if STEP.is_match(curln) {
+                // ^Never gonna turn [A-Za-z_]\\w* (up|down)$
+                let slice = &curln[17..]; // [A-Za-z_]\\w* (up|down)
+                let index = slice.find(' ').unwrap();
+                let kw = if &slice[(index + 1)..] == "up" { "INCREMENT" } else { "DECREMENT" };
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from(kw)));
+                self.lexed.push(Token::Name(self.ptr + 1, String::from(&slice[..index])));
+            } else if ASSIGN_INDEX.is_match(curln) {
+                // ^Never gonna give [A-Za-z_]\\w*(:(\\w+|~\\d+))+ .+$
+                let slice = String::from(&curln[17..]); // [A-Za-z_]\\w*(:(\\w+|~\\d+))+ .+
+                let index = slice.find(' ').unwrap();
+                let target: Vec<&str> = slice[..index].split(':').collect();
+                let expr = String::from(&slice[(index + 1)..]);
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from("ASSIGN_INDEX")));
+                self.lexed
+                    .push(Token::Name(self.ptr + 1, String::from(target[0])));
+                // each index is preceded by ":"
+                for idx in &target[1..] {
+                    // a negative index (~N) is a single value token
+                    let tokens = if let Some(digits) = idx.strip_prefix('~') {
+                        match format!("-{}", digits).parse::<i32>() {
+                            Ok(val) => vec![Token::Value(self.ptr + 1, RickrollObject::Int(val))],
+                            Err(_) => {
+                                return Err(Error::new(
+                                    ErrorType::IllegalArgumentError,
+                                    &format!("Invalid index {}", idx)[..],
+                                    Some(self.ptr + 1),
+                                ));
+                            }
+                        }
+                    } else {
+                        self.lex_expr(String::from(*idx))?
+                    };
+                    self.lexed.push(Token::Punc(self.ptr + 1, String::from(":")));
+                    for token in tokens {
+                        self.lexed.push(token);
+                    }
+                }
+                let tokens = self.lex_expr(expr)?;
+                for token in tokens {
+                    self.lexed.push(token);
+                }
             } else if ASSIGN.is_match(curln) {
-                // ^Never gonna give \\w+ .+$
+                // ^Never gonna give [A-Za-z_]\\w* .+$
                 let slice = String::from(&curln[17..]); // \\w .+
                 match slice.find(' ') {
                     Some(index) => {
                         let varname = String::from(String::from(&slice[..index]).trim());
                         let expr = String::from(&slice[(index + 1)..]);
-                        let tokens =
-                            self.wrap_check(ExprLexer::new(expr, self.ptr + 1).make_tokens())?;
+                        let tokens = self.lex_expr(expr)?;
                         self.lexed
                             .push(Token::Statement(self.ptr + 1, String::from("ASSIGN")));
                         self.lexed.push(Token::Name(self.ptr + 1, varname));
@@ -178,16 +586,84 @@ impl Lexer {
             } else if CHECK.is_match(curln) {
                 // ^Inside we both know .+$
                 let expr = String::from(&curln[20..]);
-                let tokens = self.wrap_check(ExprLexer::new(expr, self.ptr + 1).make_tokens())?;
+                let tokens = self.lex_expr(expr)?;
                 self.lexed
                     .push(Token::Statement(self.ptr + 1, String::from("CHECK")));
                 for token in tokens {
                     self.lexed.push(token);
                 }
+            } else if FOR.is_match(curln) {
+                // ^We\'ve known each other for [A-Za-z_]\\w* from .+ to .+$
+                let slice = &curln[27..]; // [A-Za-z_]\\w* from .+ to .+
+                let ind = slice.find(' ').unwrap();
+                let varname = String::from(&slice[..ind]);
+                let slice = &slice[(ind + 6)..]; // .+ to .+
+                // the first " to " outside of a literal separates the bounds
+                let ind = match Lexer::find_unquoted(slice, " to ") {
+                    Some(ind) => ind,
+                    None => {
+                        return Err(Error::new(
+                            ErrorType::SyntaxError,
+                            "Illegal statement",
+                            Some(self.ptr + 1),
+                        ));
+                    }
+                };
+                let start = self.lex_expr(String::from(&slice[..ind]))?;
+                let end = self.lex_expr(String::from(&slice[(ind + 4)..]))?;
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from("FOR")));
+                self.lexed.push(Token::Name(self.ptr + 1, varname));
+                for token in start {
+                    self.lexed.push(token);
+                }
+                self.lexed.push(Token::Punc(self.ptr + 1, String::from("to")));
+                for token in end {
+                    self.lexed.push(token);
+                }
             } else if WHILE_END.is_match(curln) {
                 // ^We know the game and we\'re gonna play it$
                 self.lexed
                     .push(Token::Statement(self.ptr + 1, String::from("WHILE_END")));
+            } else if DO.is_match(curln) {
+                // ^I just wanna tell you how I\'m feeling$
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from("DO")));
+            } else if DO_END.is_match(curln) {
+                // ^Gotta make you understand .+$
+                let expr = String::from(&curln[26..]);
+                let tokens = self.lex_expr(expr)?;
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from("DO_END")));
+                for token in tokens {
+                    self.lexed.push(token);
+                }
+            } else if MATCH.is_match(curln) {
+                // ^A full commitment\'s what I\'m thinking of .+$
+                let expr = String::from(&curln[41..]);
+                let tokens = self.lex_expr(expr)?;
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from("MATCH")));
+                for token in tokens {
+                    self.lexed.push(token);
+                }
+            } else if DEFAULT.is_match(curln) {
+                // ^You wouldn\'t get this from any other guy$
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from("DEFAULT")));
+            } else if CASE.is_match(curln) {
+                // ^You wouldn\'t get this from .+$
+                let expr = String::from(&curln[27..]);
+                let tokens = self.lex_expr(expr)?;
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from("CASE")));
+                for token in tokens {
+                    self.lexed.push(token);
+                }
+            } else if ELSE.is_match(curln) {
+                // ^Don\'t tell me you\'re too blind to see$
+                self.lexed
+                    .push(Token::Statement(self.ptr + 1, String::from("ELSE")));
             } else if IF_END.is_match(curln) {
                 // ^Your heart\'s been aching but you\'re too shy to say it$
                 self.lexed
@@ -203,7 +679,7 @@ impl Lexer {
                 self.lexed
                     .push(Token::Name(self.ptr + 1, String::from("[INTRO]")));
             } else if VERSE.is_match(curln) {
-                // ^\\[Verse \\w+\\]$
+                // ^\\[Verse [A-Za-z_]\\w*\\]$
                 let func_name = String::from(&curln[7..(curln.len() - 1)]);
                 self.ptr += 1;
                 let curln = self.raw[self.ptr].trim();
@@ -214,60 +690,71 @@ impl Lexer {
                         Some(self.ptr + 1),
                     ));
                 }
-                // "\\(Ooh give you .+\\)"
-                let func_args = self.split_vars(
-                    String::from(&curln[14..(curln.len() - 1)]),
+                // "^\\(Ooh give you(\\s.*)?\\)$"
+                let func_args = self.split_params(
+                    String::from(&curln[13..(curln.len() - 1)]),
                     String::from("up"),
                 )?;
                 self.lexed
                     .push(Token::Statement(self.ptr, String::from("VERSE")));
                 self.lexed.push(Token::Name(self.ptr, func_name));
-                for arg in func_args {
-                    self.lexed.push(Token::Name(self.ptr + 1, arg));
+                for token in func_args {
+                    self.lexed.push(token);
                 }
             } else if RUN.is_match(curln) {
-                // ^Never gonna run \\w+ and desert .+$
-                let substring = String::from(&curln[16..]); // \\w+ and desert .+$
+                // ^Never gonna run [A-Za-z_]\\w* and desert(\\s.*)?$
+                let substring = String::from(&curln[16..]); // [A-Za-z_]\\w* and desert(\\s.*)?$
                 let ind = substring.find(' ').unwrap();
                 // get function info
                 let func_name = String::from(&substring[..ind]);
                 let func_args =
-                    self.split_vars(String::from(&substring[(ind + 12)..]), String::from("you"))?;
+                    self.split_args(String::from(&substring[(ind + 11)..]), String::from("you"))?;
                 // push function call
                 self.lexed
                     .push(Token::Statement(self.ptr + 1, String::from("RUN")));
                 self.lexed.push(Token::Name(self.ptr + 1, func_name));
-                for arg in func_args {
-                    self.lexed.push(Token::Name(self.ptr + 1, arg));
+                for token in func_args {
+                    self.lexed.push(token);
                 }
             } else if RUN_ASSIGN.is_match(curln) {
-                // ^\\(Ooh give you \\w+\\) Never gonna run \\w+ and desert .+$
-                let substring = String::from(&curln[14..]); // \\w+\\) Never gonna run \\w+ and desert .+$
+                // ^\\(Ooh give you [A-Za-z_]\\w*(\\s*,\\s*[A-Za-z_]\\w*)*\\) Never gonna run [A-Za-z_]\\w* and desert(\\s.*)?$
+                let substring = String::from(&curln[14..]); // [A-Za-z_]\\w*(\\s*,\\s*[A-Za-z_]\\w*)*\\) Never gonna run [A-Za-z_]\\w* and desert(\\s.*)?$
                 let ind = substring.find(')').unwrap();
-                // get variable info
-                let varname = String::from(&substring[..ind]);
-                let substring = String::from(&substring[(ind + 18)..]); // \\w+ and desert .+$
+                // get variable info, several variables unpack an array
+                let varnames = self.split_vars(String::from(&substring[..ind]), String::new())?;
+                let substring = String::from(&substring[(ind + 18)..]); // [A-Za-z_]\\w* and desert(\\s.*)?$
                 let ind = substring.find(' ').unwrap();
                 // get function info
                 let func_name = String::from(&substring[..ind]);
                 let func_args =
-                    self.split_vars(String::from(&substring[(ind + 12)..]), String::from("you"))?;
+                    self.split_args(String::from(&substring[(ind + 11)..]), String::from("you"))?;
                 // push function call
                 self.lexed
                     .push(Token::Statement(self.ptr + 1, String::from("RUN_ASSIGN")));
-                self.lexed.push(Token::Name(self.ptr + 1, varname));
+                for (ind, varname) in varnames.into_iter().enumerate() {
+                    if ind > 0 {
+                        self.lexed.push(Token::Punc(self.ptr + 1, String::from(",")));
+                    }
+                    self.lexed.push(Token::Name(self.ptr + 1, varname));
+                }
                 self.lexed.push(Token::Name(self.ptr + 1, func_name));
-                for arg in func_args {
-                    self.lexed.push(Token::Name(self.ptr + 1, arg));
+                for token in func_args {
+                    self.lexed.push(token);
                 }
             } else if RETURN.is_match(curln) {
                 // ^\\(Ooh\\) Never gonna give, never gonna give \\(give you .+\\)$
-                let expr = String::from(&curln[51..(curln.len() - 1)]);
-                let tokens = self.wrap_check(ExprLexer::new(expr, self.ptr + 1).make_tokens())?;
+                let expr = &curln[51..(curln.len() - 1)];
                 self.lexed
                     .push(Token::Statement(self.ptr + 1, String::from("RETURN")));
-                for token in tokens {
-                    self.lexed.push(token);
+                // several values are separated by "," tokens
+                for (ind, value) in Lexer::split_unquoted(expr).iter().enumerate() {
+                    if ind > 0 {
+                        self.lexed.push(Token::Punc(self.ptr + 1, String::from(",")));
+                    }
+                    let tokens = self.lex_expr(String::from(*value))?;
+                    for token in tokens {
+                        self.lexed.push(token);
+                    }
                 }
             } else {
                 // unknown statement
@@ -279,7 +766,7 @@ impl Lexer {
             }
             self.ptr += 1;
         }
-        return Ok(self.lexed);
+        return Ok((self.lexed, self.aliases));
     }
 }
 
@@ -2,6 +2,30 @@ pub mod error;
 pub mod expr;
 pub mod interpreter;
 pub mod lexer;
+pub mod lint;
 pub mod parser;
 pub mod util;
 pub mod stdlib;
+pub mod testing;
+
+use error::Error;
+use interpreter::Interpreter;
+use lexer::Lexer;
+use parser::Parser;
+use stdlib::Builtins;
+
+// runs a program with the given standard input and returns everything it printed
+// output printed before an error is lost, use the Interpreter directly to keep it
+pub fn run_source(src: &str, stdin: &str) -> Result<String, Error> {
+    return run_source_with_builtins(src, stdin, &Builtins::new());
+}
+
+// like run_source, with custom builtins that the program can call
+pub fn run_source_with_builtins(src: &str, stdin: &str, builtins: &Builtins) -> Result<String, Error> {
+    let tokens = Lexer::new(String::from(src)).parse()?;
+    let functions = Parser::new(tokens).with_builtins(builtins).parse()?;
+    let (output, _) = Interpreter::new(functions)
+        .with_builtins(builtins)
+        .run_to_string(stdin)?;
+    return Ok(output);
+}
@@ -0,0 +1,440 @@
+use crate::expr::Expr;
+use crate::parser::ASTNode;
+use crate::util::Var;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+
+/*
+ * Lints are warnings about code that runs, but is probably a mistake.
+ * They are found by walking the parsed functions, without running them.
+ */
+
+// a problem found by a lint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Warning on line {}: {}", self.line, self.message)
+    }
+}
+
+// a declared variable, and whether it is read anywhere
+struct Local {
+    name: String,
+    line: usize,
+    kind: &'static str, // "variable" or "parameter"
+    read: bool,
+}
+
+// finds local variables and parameters that are never read
+struct UnusedLocals {
+    scopes: Vec<Vec<Local>>,
+    warnings: Vec<Warning>,
+}
+
+impl UnusedLocals {
+    fn declare(&mut self, name: &str, line: usize, kind: &'static str) {
+        self.scopes.last_mut().unwrap().push(Local {
+            name: String::from(name),
+            line,
+            kind,
+            read: false,
+        });
+    }
+
+    // marks the innermost variable with the name as read
+    // names that aren't found are globals
+    fn read(&mut self, name: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(local) = scope.iter_mut().rev().find(|x| x.name == name) {
+                local.read = true;
+                return;
+            }
+        }
+    }
+
+    fn read_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Value(_) => (),
+            Expr::Name(var) => self.read(&var.name),
+            Expr::Operation(_, args) | Expr::Call(_, args) => {
+                for arg in args {
+                    self.read_expr(arg);
+                }
+            }
+            Expr::Conditional(cond, then, other) => {
+                self.read_expr(cond);
+                self.read_expr(then);
+                self.read_expr(other);
+            }
+        }
+    }
+
+    fn pop(&mut self) {
+        for local in self.scopes.pop().unwrap() {
+            if !local.read {
+                self.warnings.push(Warning {
+                    line: local.line,
+                    message: format!("{} {} is never read", local.kind, local.name),
+                });
+            }
+        }
+    }
+
+    // walks the statements of a block in a new scope
+    fn block(&mut self, body: &[ASTNode]) {
+        self.scopes.push(Vec::new());
+        for node in body {
+            self.statement(node);
+        }
+        self.pop();
+    }
+
+    fn statement(&mut self, node: &ASTNode) {
+        match node {
+            ASTNode::Say(_, expr) => self.read_expr(expr),
+            ASTNode::Let(ln, name) => self.declare(name, *ln, "variable"),
+            ASTNode::Assign(_, _, expr) => self.read_expr(expr),
+            ASTNode::AssignIndex(_, var, indices, expr) => {
+                // the rest of the array is kept, so it is read
+                self.read(&var.name);
+                for index in indices {
+                    self.read_expr(index);
+                }
+                self.read_expr(expr);
+            }
+            ASTNode::Step(_, var, _) => self.read(&var.name),
+            ASTNode::If(_, cond, body, else_body) => {
+                self.read_expr(cond);
+                self.block(body);
+                self.block(else_body);
+            }
+            ASTNode::While(_, cond, body) => {
+                self.read_expr(cond);
+                self.block(body);
+            }
+            ASTNode::DoWhile(_, _, cond, body) => {
+                self.block(body);
+                self.read_expr(cond);
+            }
+            ASTNode::For(ln, var, start, end, body) => {
+                self.read_expr(start);
+                self.read_expr(end);
+                // loops often only count, so their variable doesn't need to be read
+                self.scopes.push(Vec::new());
+                self.declare(var, *ln, "variable");
+                self.read(var);
+                self.block(body);
+                self.pop();
+            }
+            ASTNode::Match(_, subject, cases, default) => {
+                self.read_expr(subject);
+                for (_, body) in cases {
+                    self.block(body);
+                }
+                self.block(default);
+            }
+            ASTNode::Return(_, values) => {
+                for value in values {
+                    self.read_expr(value);
+                }
+            }
+            ASTNode::Labelled(_, _, node) => self.statement(node),
+            ASTNode::Run(_, _, args) | ASTNode::RunAssign(_, _, _, args) => {
+                for arg in args {
+                    self.read_expr(arg);
+                }
+            }
+            ASTNode::Break(_, _) | ASTNode::Function(..) => (),
+        }
+    }
+}
+
+// finds assignments whose value is always overwritten before it is read
+// the analysis walks each function backwards, keeping the set of locals
+// that are assigned again on every path before being read ("killed")
+struct DeadAssignments {
+    locals: HashSet<String>,
+    // the killed set at the exit of each enclosing loop, for breaks
+    loops: Vec<(Option<String>, HashSet<String>)>,
+    // label of the loop that is about to be walked
+    label: Option<String>,
+    // loops are walked several times, but only warn on the last walk
+    report: bool,
+    warnings: Vec<Warning>,
+}
+
+impl DeadAssignments {
+    fn read_expr(killed: &mut HashSet<String>, expr: &Expr) {
+        match expr {
+            Expr::Value(_) => (),
+            Expr::Name(var) => {
+                killed.remove(&var.name);
+            }
+            Expr::Operation(_, args) | Expr::Call(_, args) => {
+                for arg in args {
+                    DeadAssignments::read_expr(killed, arg);
+                }
+            }
+            // either value may be read
+            Expr::Conditional(cond, then, other) => {
+                DeadAssignments::read_expr(killed, cond);
+                DeadAssignments::read_expr(killed, then);
+                DeadAssignments::read_expr(killed, other);
+            }
+        }
+    }
+
+    // returns the killed set before a block, given the one after it
+    fn block(&mut self, body: &[ASTNode], after: &HashSet<String>) -> HashSet<String> {
+        let mut killed = after.clone();
+        for node in body.iter().rev() {
+            killed = self.statement(node, killed, after);
+        }
+        return killed;
+    }
+
+    // returns the killed sets before the condition and before the body of a loop,
+    // given the one after the loop
+    fn walk_loop(
+        &mut self,
+        cond: &Expr,
+        body: &[ASTNode],
+        after: &HashSet<String>,
+    ) -> (HashSet<String>, HashSet<String>) {
+        let label = self.label.take();
+        self.loops.push((label, after.clone()));
+        let report = self.report;
+        self.report = false;
+        // start with everything that is killed after the loop, and shrink it until it is stable
+        let mut head = after.clone();
+        DeadAssignments::read_expr(&mut head, cond);
+        loop {
+            let mut next: HashSet<String> = self
+                .block(body, &head)
+                .intersection(after)
+                .cloned()
+                .collect();
+            DeadAssignments::read_expr(&mut next, cond);
+            if next == head {
+                break;
+            }
+            head = next;
+        }
+        // walk the body once more with the final state to find its dead assignments
+        self.report = report;
+        let start = self.block(body, &head);
+        self.loops.pop();
+        return (head, start);
+    }
+
+    fn statement(
+        &mut self,
+        node: &ASTNode,
+        mut killed: HashSet<String>,
+        after_block: &HashSet<String>,
+    ) -> HashSet<String> {
+        match node {
+            ASTNode::Say(_, expr) => DeadAssignments::read_expr(&mut killed, expr),
+            ASTNode::Let(_, name) => {
+                // statements before the declaration use the variable outside of the block
+                if after_block.contains(name) {
+                    killed.insert(name.clone());
+                } else {
+                    killed.remove(name);
+                }
+            }
+            ASTNode::Assign(ln, Var { name, .. }, expr) => {
+                if self.locals.contains(name) {
+                    if self.report && killed.contains(name) {
+                        self.warnings.push(Warning {
+                            line: *ln,
+                            message: format!(
+                                "value assigned to {} is overwritten before it is read",
+                                name
+                            ),
+                        });
+                    }
+                    killed.insert(name.clone());
+                }
+                DeadAssignments::read_expr(&mut killed, expr);
+            }
+            ASTNode::AssignIndex(_, var, indices, expr) => {
+                // the rest of the array is kept, so it is read
+                killed.remove(&var.name);
+                for index in indices {
+                    DeadAssignments::read_expr(&mut killed, index);
+                }
+                DeadAssignments::read_expr(&mut killed, expr);
+            }
+            ASTNode::Step(_, var, _) => {
+                killed.remove(&var.name);
+            }
+            ASTNode::If(_, cond, body, else_body) => {
+                let then_killed = self.block(body, &killed);
+                let else_killed = self.block(else_body, &killed);
+                killed = then_killed.intersection(&else_killed).cloned().collect();
+                DeadAssignments::read_expr(&mut killed, cond);
+            }
+            ASTNode::While(_, cond, body) => killed = self.walk_loop(cond, body, &killed).0,
+            // the body runs once before the condition is checked
+            ASTNode::DoWhile(_, _, cond, body) => killed = self.walk_loop(cond, body, &killed).1,
+            ASTNode::For(_, var, start, end, body) => {
+                // the loop reads its variable before every iteration
+                let after = killed.clone();
+                let read = Expr::Name(Var {
+                    name: var.clone(),
+                    slot: None,
+                });
+                killed = self.walk_loop(&read, body, &after).0;
+                // the variable only exists in the loop
+                if after.contains(var) {
+                    killed.insert(var.clone());
+                } else {
+                    killed.remove(var);
+                }
+                DeadAssignments::read_expr(&mut killed, end);
+                DeadAssignments::read_expr(&mut killed, start);
+            }
+            ASTNode::Match(_, subject, cases, default) => {
+                let mut all = self.block(default, &killed);
+                for (_, body) in cases {
+                    let case = self.block(body, &killed);
+                    all = all.intersection(&case).cloned().collect();
+                }
+                killed = all;
+                DeadAssignments::read_expr(&mut killed, subject);
+            }
+            ASTNode::Return(_, values) => {
+                killed.clear();
+                for value in values {
+                    DeadAssignments::read_expr(&mut killed, value);
+                }
+            }
+            ASTNode::Break(_, label) => {
+                let target = match label {
+                    Some(label) => self
+                        .loops
+                        .iter()
+                        .rev()
+                        .find(|x| x.0.as_ref() == Some(label)),
+                    None => self.loops.last(),
+                };
+                killed = target.map_or(HashSet::new(), |x| x.1.clone());
+            }
+            ASTNode::Labelled(_, label, node) => {
+                self.label = Some(label.clone());
+                killed = self.statement(node, killed, after_block);
+                self.label = None;
+            }
+            ASTNode::Run(_, _, args) => {
+                for arg in args {
+                    DeadAssignments::read_expr(&mut killed, arg);
+                }
+            }
+            ASTNode::RunAssign(_, names, _, args) => {
+                for name in names {
+                    if self.locals.contains(name) {
+                        killed.insert(name.clone());
+                    }
+                }
+                for arg in args {
+                    DeadAssignments::read_expr(&mut killed, arg);
+                }
+            }
+            ASTNode::Function(..) => (),
+        }
+        return killed;
+    }
+}
+
+// finds local variables and parameters that are never read in their function
+// the intro block is skipped, since its variables are globals
+pub fn unused_locals(functions: &HashMap<String, ASTNode>) -> Vec<Warning> {
+    let mut lint = UnusedLocals {
+        scopes: Vec::new(),
+        warnings: Vec::new(),
+    };
+    for (name, function) in functions {
+        if name == "[INTRO]" {
+            continue;
+        }
+        if let ASTNode::Function(ln, _, args, defaults, body) = function {
+            lint.scopes.push(Vec::new());
+            let required = args.len() - defaults.len();
+            for (ind, arg) in args.iter().enumerate() {
+                // a default can read the parameters before it
+                if ind >= required {
+                    lint.read_expr(&defaults[ind - required]);
+                }
+                lint.declare(arg, *ln, "parameter");
+            }
+            lint.block(body);
+            lint.pop();
+        }
+    }
+    return lint.warnings;
+}
+
+// finds assignments to locals that are overwritten on every path before they are read
+// like unused_locals, the intro block is skipped
+pub fn dead_assignments(functions: &HashMap<String, ASTNode>) -> Vec<Warning> {
+    let mut lint = DeadAssignments {
+        locals: HashSet::new(),
+        loops: Vec::new(),
+        label: None,
+        report: true,
+        warnings: Vec::new(),
+    };
+    for (name, function) in functions {
+        if name == "[INTRO]" {
+            continue;
+        }
+        if let ASTNode::Function(_, _, args, _, body) = function {
+            lint.locals = args.iter().cloned().collect();
+            declared_names(body, &mut lint.locals);
+            // nothing is overwritten after the function returns
+            lint.block(body, &HashSet::new());
+        }
+    }
+    return lint.warnings;
+}
+
+// adds the names of the variables declared anywhere in a block
+fn declared_names(body: &[ASTNode], names: &mut HashSet<String>) {
+    for node in body {
+        match node {
+            ASTNode::Let(_, name) => {
+                names.insert(name.clone());
+            }
+            ASTNode::If(_, _, body, else_body) => {
+                declared_names(body, names);
+                declared_names(else_body, names);
+            }
+            ASTNode::While(_, _, body)
+            | ASTNode::DoWhile(_, _, _, body)
+            | ASTNode::For(_, _, _, _, body) => declared_names(body, names),
+            ASTNode::Match(_, _, cases, default) => {
+                for (_, body) in cases {
+                    declared_names(body, names);
+                }
+                declared_names(default, names);
+            }
+            ASTNode::Labelled(_, _, node) => declared_names(std::slice::from_ref(&**node), names),
+            _ => (),
+        }
+    }
+}
+
+// runs every lint, returning the warnings in the order of their lines
+pub fn lint(functions: &HashMap<String, ASTNode>) -> Vec<Warning> {
+    let mut warnings = unused_locals(functions);
+    warnings.append(&mut dead_assignments(functions));
+    warnings.sort_by_key(|x| x.line);
+    return warnings;
+}
@@ -1,11 +1,22 @@
-use rickroll::lexer::Lexer;
-use rickroll::parser::Parser;
+use rickroll::lexer::{split_lines, Aliases, Lexer};
+use rickroll::lint;
+use rickroll::parser::{ParsedInput, Parser};
 use rickroll::interpreter::Interpreter;
+use rickroll::testing;
+use rickroll::util::Scope;
 
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs::File;
 use std::io::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
+use signal_hook::consts::SIGINT;
 use structopt::StructOpt;
 use ansi_term::Colour::Red;
 
@@ -13,68 +24,474 @@ use ansi_term::Colour::Red;
 struct Opt {
     #[structopt(short, long, about="Print debugging information")]
     debug: bool,
+    #[structopt(long, about="Print debugging events as newline-delimited JSON")]
+    debug_json: bool,
+    #[structopt(long, about="Print the tokens made by the lexer and exit")]
+    tokens: bool,
+    #[structopt(long, about="Print the parsed functions and exit")]
+    ast: bool,
+    #[structopt(long, about="Disable the Sleep built-in function")]
+    no_sleep: bool,
+    #[structopt(long, about="Treat nonzero INT conditions as TRUE")]
+    int_conditions: bool,
+    #[structopt(long, about="Replace invalid UTF-8 input instead of failing")]
+    lossy_input: bool,
+    #[structopt(long, about="Maximum length of arrays and strings made or stored by a program")]
+    max_collection_size: Option<usize>,
+    #[structopt(long, about="Approximate maximum number of bytes used by all variables")]
+    max_memory: Option<usize>,
+    #[structopt(long, about="Warn on stderr about code that is probably a mistake")]
+    lint: bool,
+    #[structopt(long, about="Warn on stderr when UNDEFINED is printed")]
+    warn_undefined: bool,
+    #[structopt(long, about="Print each executed statement to stderr")]
+    trace: bool,
+    #[structopt(long, about="Run the program one statement at a time with a debugging prompt")]
+    debug_repl: bool,
+    #[structopt(long, about="Disable colored output")]
+    no_color: bool,
+    #[structopt(long, about="Run test programs against their expected output")]
+    test: bool,
+    #[structopt(long, about="Start an interactive session (the default when no file is given)")]
+    repl: bool,
     #[structopt(parse(from_os_str))]
-    file: PathBuf,
+    file: Option<PathBuf>,
 }
 
-fn execute(file: PathBuf, debug: bool) -> std::result::Result<(), Error> {
+// colors are used only when stderr is a terminal and NO_COLOR is not set
+fn use_color(no_color: bool) -> bool {
+    !no_color && env::var_os("NO_COLOR").is_none() && atty::is(atty::Stream::Stderr)
+}
+
+// paints a message red if coloring is enabled
+fn paint(msg: &str, color: bool) -> String {
+    if color {
+        Red.paint(msg).to_string()
+    } else {
+        String::from(msg)
+    }
+}
+
+// escapes a string for use in JSON
+fn json_string(raw: &str) -> String {
+    let mut res = String::from("\"");
+    for chr in raw.chars() {
+        match chr {
+            '"' => res += "\\\"",
+            '\\' => res += "\\\\",
+            '\n' => res += "\\n",
+            '\r' => res += "\\r",
+            '\t' => res += "\\t",
+            chr if chr.is_control() => res += &format!("\\u{:04x}", chr as u32),
+            chr => res.push(chr),
+        }
+    }
+    res.push('"');
+    res
+}
+
+// prints a debugging event as one line of JSON
+// fields are (key, already encoded JSON value)
+fn json_event(stage: &str, message: &str, fields: &[(&str, String)]) {
+    let mut res = format!(
+        "{{\"stage\":{},\"message\":{}",
+        json_string(stage),
+        json_string(message)
+    );
+    for (key, val) in fields {
+        res += &format!(",{}:{}", json_string(key), val);
+    }
+    res += "}";
+    eprintln!("{}", res);
+}
+
+// milliseconds elapsed since start, as a JSON number
+fn elapsed_ms(start: &Instant) -> String {
+    format!("{:.3}", start.elapsed().as_secs_f64() * 1000.0)
+}
+
+// runs the tests at path, returning whether all of them passed
+// a test that is interrupted fails, and the remaining ones aren't run
+fn run_tests(path: &Path, color: bool, interrupted: &Arc<AtomicBool>) -> bool {
+    let results = match testing::run_tests_with_interrupt(path, interrupted) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("{}", paint(&format!("{}", e), color));
+            return false;
+        }
+    };
+    let mut failed = 0;
+    for result in results.iter() {
+        match &result.failure {
+            None => println!("PASS {}", result.program.display()),
+            Some(msg) => {
+                failed += 1;
+                println!("{}", paint(&format!("FAIL {}: {}", result.program.display(), msg), color));
+            }
+        }
+    }
+    println!("{} passed, {} failed", results.len() - failed, failed);
+    return failed == 0;
+}
+
+// prints a prompt and reads a line typed by the user, None at the end of input
+fn prompt(text: &str, reader: &mut dyn BufRead) -> Option<String> {
+    print!("{}", text);
+    stdout().flush().expect("Error when writing to stdout");
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) | Err(_) => return None,
+        Ok(_) => return Some(String::from(line.trim_end_matches(&['\n', '\r'][..]))),
+    }
+}
+
+// parses an input of the interactive session, None if a block is still open
+// the aliases defined by a parsed input are kept for the next ones
+fn parse_input(
+    parser: &mut Parser,
+    aliases: &mut Aliases,
+    src: &str,
+) -> std::result::Result<Option<ParsedInput>, rickroll::error::Error> {
+    let lexer = Lexer::new(String::from(src)).with_aliases(aliases.clone());
+    let (tokens, defined) = match lexer.parse_with_aliases() {
+        Ok(res) => res,
+        Err(err) => {
+            // a single line that isn't a statement is printed as an expression
+            if !src.contains('\n') {
+                let lexer = Lexer::new(format!("Never gonna say {}", src)).with_aliases(aliases.clone());
+                if let Ok(tokens) = lexer.parse() {
+                    if let Ok(Some(parsed)) = parser.parse_input(tokens) {
+                        return Ok(Some(parsed));
+                    }
+                }
+            }
+            return Err(err);
+        }
+    };
+    let res = parser.parse_input(tokens);
+    if let Ok(Some(_)) = &res {
+        *aliases = defined;
+    }
+    return res;
+}
+
+// runs an interactive session, keeping variables and verses between inputs
+// returns the exit code, which is only nonzero if Exit was called
+fn repl(opt: &Opt, color: bool, interrupted: &Arc<AtomicBool>) -> i32 {
+    let mut parser = Parser::new(Vec::new()).with_int_conditions(opt.int_conditions);
+    let mut aliases: Aliases = Vec::new();
+    let mut interpreter = Interpreter::new(HashMap::new())
+        .with_sleep(!opt.no_sleep)
+        .with_int_conditions(opt.int_conditions)
+        .with_lossy_input(opt.lossy_input)
+        .with_max_collection_size(opt.max_collection_size)
+        .with_max_memory(opt.max_memory)
+        .with_interrupt(Arc::clone(interrupted));
+    if opt.warn_undefined {
+        interpreter = interpreter.with_undefined_warnings(Box::new(stderr()));
+    }
+    let stdin = stdin();
+    // programs read their input from the same stream as the session
+    let mut reader = stdin.lock();
+    while let Some(mut src) = prompt("> ", &mut reader) {
+        if src.trim().is_empty() {
+            continue;
+        }
+        // a verse ends at a blank line
+        if src.trim_start().starts_with('[') {
+            while let Some(line) = prompt("... ", &mut reader) {
+                if line.trim().is_empty() {
+                    break;
+                }
+                src += "\n";
+                src += &line;
+            }
+        }
+        let parsed = loop {
+            match parse_input(&mut parser, &mut aliases, &src) {
+                Ok(Some(parsed)) => break Some(parsed),
+                // an open block continues on the next line
+                Ok(None) => match prompt("... ", &mut reader) {
+                    Some(line) => {
+                        src += "\n";
+                        src += &line;
+                    }
+                    None => return 0,
+                },
+                Err(e) => {
+                    eprintln!("{}", paint(&format!("{}", e), color));
+                    break None;
+                }
+            }
+        };
+        if let Some((functions, statements)) = parsed {
+            // Ctrl-C only stops the input that is running when it is pressed
+            interrupted.store(false, Ordering::Relaxed);
+            let res = interpreter.run_input(functions, &statements, &mut stdout(), &mut reader);
+            if let Err(e) = res {
+                eprintln!("{}", paint(&format!("{}", e), color));
+            }
+            if let Some(code) = interpreter.exit_code() {
+                return code;
+            }
+        }
+    }
+    return 0;
+}
+
+// makes Ctrl-C set the returned flag instead of killing the process, so a program
+// can stop with an error after its output is flushed
+// a second Ctrl-C kills it in case it is stuck in a builtin
+fn handle_interrupts() -> Arc<AtomicBool> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    // the shutdown is registered first, so it sees the flag before the same signal sets it
+    let registered = signal_hook::flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&interrupted))
+        .and_then(|_| signal_hook::flag::register(SIGINT, Arc::clone(&interrupted)));
+    if let Err(e) = registered {
+        eprintln!("Could not handle Ctrl-C: {}", e);
+    }
+    return interrupted;
+}
+
+// state of an interactive debugging session, which pauses before statements
+struct DebugSession {
+    lines: Vec<String>, // source code of the program
+    breakpoints: HashSet<usize>,
+    stepping: bool, // whether to pause before the next statement
+    quit: Rc<Cell<bool>>, // set when the user stops the program
+}
+
+impl DebugSession {
+    // reads commands from the program's input while paused before a statement
+    // returns false if the program should stop
+    fn pause(&mut self, ln: usize, scope: &Scope, reader: &mut dyn BufRead) -> bool {
+        if !self.stepping && !self.breakpoints.contains(&ln) {
+            return true;
+        }
+        eprintln!("line {}: {}", ln, self.lines.get(ln - 1).map_or("", |x| x.trim()));
+        loop {
+            eprint!("(debug) ");
+            let mut line = String::new();
+            if let Ok(0) | Err(_) = reader.read_line(&mut line) {
+                // the end of input quits
+                self.quit.set(true);
+                return false;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                [] | ["step"] | ["s"] => {
+                    self.stepping = true;
+                    return true;
+                }
+                ["continue"] | ["c"] => {
+                    self.stepping = false;
+                    return true;
+                }
+                ["print", name] | ["p", name] => match scope.get_var(String::from(name)) {
+                    Some(val) => eprintln!("{} = {}", name, val),
+                    None => eprintln!("No variable {}", name),
+                },
+                ["break", line] | ["b", line] => match line.parse::<usize>() {
+                    Ok(line) => {
+                        self.breakpoints.insert(line);
+                        eprintln!("Breakpoint on line {}", line);
+                    }
+                    Err(_) => eprintln!("Expected a line number after break"),
+                },
+                ["quit"] | ["q"] => {
+                    self.quit.set(true);
+                    return false;
+                }
+                _ => eprintln!("Unknown command, expected step, continue, print VAR, break LINE or quit"),
+            }
+        }
+    }
+}
+
+// runs the program or tests, returning the exit code
+fn execute(opt: Opt) -> std::result::Result<i32, Error> {
+    let color = use_color(opt.no_color);
+    let debug = opt.debug;
+    let json = opt.debug_json;
+    let interrupted = handle_interrupts();
+    if opt.test {
+        let passed = match &opt.file {
+            Some(path) => run_tests(path, color, &interrupted),
+            None => {
+                eprintln!("{}", paint("--test needs a program or a directory", color));
+                false
+            }
+        };
+        return Ok(if passed { 0 } else { 1 });
+    }
+    let path = match &opt.file {
+        Some(path) if !opt.repl => path,
+        _ => {
+            return Ok(repl(&opt, color, &interrupted));
+        }
+    };
     // read from file
-    let mut f = File::open(file)?;
+    let mut f = File::open(path)?;
     let mut raw = String::new();
     f.read_to_string(&mut raw)?;
     if debug {
-        eprintln!("{}", Red.paint("Started lexing..."));
+        eprintln!("{}", paint("Started lexing...", color));
+    }
+    if json {
+        json_event("lex", "Started lexing", &[]);
     }
-    let lexer = Lexer::new(raw);
+    let start = Instant::now();
+    let lexer = Lexer::new(raw.clone());
     let tokens = lexer.parse();
-    match tokens {
+    let tokens = match tokens {
+        Ok(tokens) => tokens,
         Err(e) => {
-            eprintln!("{}", Red.paint(format!("{}", e)));
-            return Ok(());
+            if json {
+                json_event("lex", "Failed lexing", &[("error", json_string(&e.to_string()))]);
+            } else {
+                eprintln!("{}", paint(&format!("{}", e), color));
+            }
+            return Ok(1);
         }
-        _ => (),
     };
-    let tokens = tokens.unwrap();
+    if opt.tokens {
+        // the tokens are printed even if they don't parse
+        for token in tokens.iter() {
+            println!("{:?}", token);
+        }
+        return Ok(0);
+    }
     if debug {
         println!("{:?}", tokens);
-        eprintln!("{}", Red.paint("Finished lexing..."));
-        eprintln!("{}", Red.paint("Started parsing..."));
+        eprintln!("{}", paint("Finished lexing...", color));
+        eprintln!("{}", paint("Started parsing...", color));
+    }
+    if json {
+        json_event(
+            "lex",
+            "Finished lexing",
+            &[
+                ("duration_ms", elapsed_ms(&start)),
+                ("token_count", tokens.len().to_string()),
+            ],
+        );
+        json_event("parse", "Started parsing", &[]);
     }
-    let parser = Parser::new(tokens);
+    let start = Instant::now();
+    let parser = Parser::new(tokens).with_int_conditions(opt.int_conditions);
     let parsed = parser.parse();
-    match parsed {
+    let parsed = match parsed {
+        Ok(parsed) => parsed,
         Err(e) => {
-            eprintln!("{}", Red.paint(format!("{}", e)));
-            return Ok(());
+            if json {
+                json_event("parse", "Failed parsing", &[("error", json_string(&e.to_string()))]);
+            } else {
+                eprintln!("{}", paint(&format!("{}", e), color));
+            }
+            return Ok(1);
         }
-        _ => (),
     };
-    let parsed = parsed.unwrap();
+    if opt.lint {
+        for warning in lint::lint(&parsed) {
+            eprintln!("{}", warning);
+        }
+    }
+    if opt.ast {
+        let mut names: Vec<&String> = parsed.keys().collect();
+        names.sort();
+        for name in names {
+            println!("{:#?}", parsed[name]);
+        }
+        return Ok(0);
+    }
     if debug {
         println!("{:?}", parsed);
-        eprintln!("{}", Red.paint("Finished parsing..."));
-        eprintln!("{}", Red.paint("Started executing..."));
+        eprintln!("{}", paint("Finished parsing...", color));
+        eprintln!("{}", paint("Started executing...", color));
+    }
+    if json {
+        json_event(
+            "parse",
+            "Finished parsing",
+            &[
+                ("duration_ms", elapsed_ms(&start)),
+                ("function_count", parsed.len().to_string()),
+            ],
+        );
+        json_event("execute", "Started executing", &[]);
+    }
+    let start = Instant::now();
+    let mut interpreter = Interpreter::new(parsed)
+        .with_sleep(!opt.no_sleep)
+        .with_int_conditions(opt.int_conditions)
+        .with_lossy_input(opt.lossy_input)
+        .with_max_collection_size(opt.max_collection_size)
+        .with_max_memory(opt.max_memory);
+    if opt.trace {
+        interpreter = interpreter.with_trace(&raw, Box::new(stderr()));
+    }
+    if opt.warn_undefined {
+        interpreter = interpreter.with_undefined_warnings(Box::new(stderr()));
+    }
+    interpreter = interpreter.with_interrupt(interrupted);
+    let quit = Rc::new(Cell::new(false));
+    if opt.debug_repl {
+        let mut session = DebugSession {
+            lines: split_lines(&raw),
+            breakpoints: HashSet::new(),
+            stepping: true,
+            quit: Rc::clone(&quit),
+        };
+        interpreter = interpreter
+            .with_statement_hook(Box::new(move |ln, scope, reader| session.pause(ln, scope, reader)));
     }
-    let mut interpreter = Interpreter::new(parsed);
     let result = interpreter.run(&mut stdout(), &mut BufReader::new(stdin()));
     match result {
+        // stopping in the debugger isn't a failure
+        Err(_) if quit.get() => return Ok(0),
         Err(e) => {
-            eprintln!("{}", Red.paint(format!("{}", e)));
-            return Ok(());
+            if json {
+                json_event(
+                    "execute",
+                    "Failed executing",
+                    &[
+                        ("duration_ms", elapsed_ms(&start)),
+                        ("error", json_string(&e.to_string())),
+                    ],
+                );
+            } else {
+                eprintln!("{}", paint(&format!("{}", e), color));
+            }
+            return Ok(1);
         }
         _ => (),
     }
+    if json {
+        json_event(
+            "execute",
+            "Finished executing",
+            &[
+                ("duration_ms", elapsed_ms(&start)),
+                ("result", json_string(&result.as_ref().unwrap().to_string())),
+            ],
+        );
+    }
     if debug {
         println!(
             "\n{:#?}",
             result.unwrap()
         );
     }
-    return Ok(());
+    // a program that calls Exit ends with its status
+    return Ok(interpreter.exit_code().unwrap_or(0));
 }
 
 fn main() -> std::result::Result<(), Error> {
     let opt = Opt::from_args();
-    execute(opt.file, opt.debug)?;
+    let code = execute(opt)?;
+    if code != 0 {
+        stdout().flush()?;
+        std::process::exit(code);
+    }
     return Ok(());
 }
@@ -2,7 +2,7 @@ use crate::error::*;
 use crate::expr::*;
 use crate::lexer::Token;
 use crate::util::*;
-use crate::stdlib::BUILTIN_FUNCTIONS;
+use crate::stdlib::{Builtins, BUILTIN_FUNCTIONS};
 
 use std::collections::{HashMap, HashSet, VecDeque};
 
@@ -10,13 +10,20 @@ use std::collections::{HashMap, HashSet, VecDeque};
 pub enum ASTNode {
     Say(usize, Expr),
     Let(usize, String),
-    Assign(usize, String, Expr),
-    If(usize, Expr, Vec<ASTNode>),
+    Assign(usize, Var, Expr),
+    AssignIndex(usize, Var, Vec<Expr>, Expr),
+    Step(usize, Var, i32), // adds 1 or -1 to a variable
+    If(usize, Expr, Vec<ASTNode>, Vec<ASTNode>),
     While(usize, Expr, Vec<ASTNode>),
-    Function(usize, String, Vec<String>, Vec<ASTNode>),
-    Return(usize, Expr),
-    Run(usize, String, Vec<String>),
-    RunAssign(usize, String, String, Vec<String>),
+    DoWhile(usize, usize, Expr, Vec<ASTNode>), // also has the line of the condition
+    For(usize, String, Expr, Expr, Vec<ASTNode>),
+    Match(usize, Expr, Vec<(RickrollObject, Vec<ASTNode>)>, Vec<ASTNode>), // cases and default
+    Function(usize, String, Vec<String>, Vec<Expr>, Vec<ASTNode>), // defaults of the last parameters
+    Return(usize, Vec<Expr>),
+    Break(usize, Option<String>), // optional label of the loop to leave
+    Labelled(usize, String, Box<ASTNode>),
+    Run(usize, String, Vec<Expr>),
+    RunAssign(usize, Vec<String>, String, Vec<Expr>),
 }
 
 impl ASTNode {
@@ -26,22 +33,67 @@ impl ASTNode {
             Say(ln, _) => *ln,
             Let(ln, _) => *ln,
             Assign(ln, _, _) => *ln,
-            If(ln, _, _) => *ln,
+            AssignIndex(ln, _, _, _) => *ln,
+            Step(ln, _, _) => *ln,
+            If(ln, _, _, _) => *ln,
             While(ln, _, _) => *ln,
-            Function(ln, _, _, _) => *ln,
+            DoWhile(ln, _, _, _) => *ln,
+            For(ln, _, _, _, _) => *ln,
+            Match(ln, _, _, _) => *ln,
+            Function(ln, _, _, _, _) => *ln,
             Return(ln, _) => *ln,
+            Break(ln, _) => *ln,
+            Labelled(ln, _, _) => *ln,
             Run(ln, _, _) => *ln,
             RunAssign(ln, _, _, _) => *ln,
         }
     }
 }
 
+// verses and statements of an input of an interactive session
+pub type ParsedInput = (Vec<ASTNode>, Vec<ASTNode>);
+
+// rejects break statements that aren't inside of a loop
+// labelled breaks are already checked against the loops around them
+fn check_breaks(body: &[ASTNode]) -> Result<(), Error> {
+    for node in body {
+        match node {
+            ASTNode::Break(ln, None) => {
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    "Break outside of loop",
+                    Some(*ln),
+                ));
+            }
+            ASTNode::If(_, _, body, else_body) => {
+                check_breaks(body)?;
+                check_breaks(else_body)?;
+            }
+            ASTNode::Match(_, _, cases, default) => {
+                for (_, body) in cases {
+                    check_breaks(body)?;
+                }
+                check_breaks(default)?;
+            }
+            _ => (),
+        }
+    }
+    return Ok(());
+}
+
 #[derive(Debug)]
 pub struct Parser {
     tokens: VecDeque<Token>,
     output: HashMap<String, ASTNode>,
     func_cache: HashSet<String>,
+    params: HashSet<String>, // parameters of the function being parsed
+    int_conditions: bool,    // whether INT conditions are allowed
+    labels: Vec<String>,     // labels of the loops being parsed
+    builtins: Builtins,        // custom builtins, matching Interpreter::with_builtins
     scope: Scope,
+    in_global_block: bool,     // whether statements run in the global context as they are parsed
+    initialized: HashSet<String>, // globals assigned so far by the intro or interactive inputs
+    incomplete: bool,          // whether the tokens ran out inside a block
 }
 
 impl Parser {
@@ -50,10 +102,36 @@ impl Parser {
             tokens: VecDeque::from(tokens),
             output: HashMap::new(),
             func_cache: HashSet::new(),
+            params: HashSet::new(),
+            int_conditions: false,
+            labels: Vec::new(),
+            builtins: Builtins::new(),
             scope: Scope::new(),
+            in_global_block: false,
+            initialized: HashSet::new(),
+            incomplete: false,
         }
     }
 
+    // allows INT conditions, matching Interpreter::with_int_conditions
+    pub fn with_int_conditions(mut self, int_conditions: bool) -> Parser {
+        self.int_conditions = int_conditions;
+        self
+    }
+
+    // allows calls to custom builtins, which should also be passed to Interpreter::with_builtins
+    pub fn with_builtins(mut self, builtins: &Builtins) -> Parser {
+        self.builtins = builtins.clone();
+        self
+    }
+
+    // whether a function can be called, including custom builtins
+    fn function_exists(&self, name: &str) -> bool {
+        return self.func_cache.contains(name)
+            || self.builtins.contains(name)
+            || BUILTIN_FUNCTIONS.contains_key(name);
+    }
+
     fn get_name(&mut self) -> String {
         let name = self.tokens.pop_front().unwrap();
         match name {
@@ -62,6 +140,47 @@ impl Parser {
         }
     }
 
+    // the variable a name refers to, along with its slot in the current scope
+    fn var(&self, name: String) -> Var {
+        let slot = self.scope.resolve(&name);
+        return Var { name, slot };
+    }
+
+    // the error for a block that is still open when the tokens run out
+    fn unclosed_block(&mut self) -> Error {
+        self.incomplete = true;
+        return Error::new(ErrorType::SyntaxError, "Unbalanced statements", None);
+    }
+
+    // whether the last input ended inside a block, which later lines may close
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    // rejects a global that is read by its first assignment, while it is still undefined
+    // only statements of the intro and interactive inputs are known to run in order
+    fn check_initializer(&mut self, name: &str, expr: &Expr, line: usize) -> Result<(), Error> {
+        if !self.in_global_block || !self.scope.is_global(String::from(name)) {
+            return Ok(());
+        }
+        if !self.initialized.contains(name) && expr.uses_var(name) {
+            return Err(Error::new(
+                ErrorType::NameError,
+                &format!("Global {} used in its own initializer while undefined", name)[..],
+                Some(line),
+            ));
+        }
+        self.initialized.insert(String::from(name));
+        return Ok(());
+    }
+
+    // makes an expression parser that knows the declared variables and functions
+    fn expr_parser(&self, tokens: Vec<Token>) -> ExprParser {
+        return ExprParser::new(tokens, self.scope.clone())
+            .with_functions(self.func_cache.clone())
+            .with_builtins(&self.builtins);
+    }
+
     fn parse_expr(&mut self) -> Result<Expr, Error> {
         let mut expr_tokens: Vec<Token> = Vec::new();
         while !self.tokens.is_empty() {
@@ -70,27 +189,101 @@ impl Parser {
             }
             expr_tokens.push(self.tokens.pop_front().unwrap());
         }
-        let parser = ExprParser::new(expr_tokens, self.scope.clone());
+        let parser = self.expr_parser(expr_tokens);
         return parser.parse();
     }
 
+    // parses the arguments of a function call, which are separated by ","
+    // commas inside parentheses belong to calls in the arguments
+    fn parse_args(&mut self) -> Result<Vec<Expr>, Error> {
+        let mut args: Vec<Expr> = Vec::new();
+        let mut arg_tokens: Vec<Token> = Vec::new();
+        let mut depth = 0;
+        while !self.tokens.is_empty() {
+            if let Token::Statement(_, _) = self.tokens.front().unwrap() {
+                break;
+            }
+            let token = self.tokens.pop_front().unwrap();
+            if let Token::Punc(_, punc) = &token {
+                match &punc[..] {
+                    "(" => depth += 1,
+                    ")" => depth -= 1,
+                    "," if depth == 0 => {
+                        args.push(self.expr_parser(arg_tokens).parse()?);
+                        arg_tokens = Vec::new();
+                        continue;
+                    }
+                    _ => (),
+                }
+            }
+            arg_tokens.push(token);
+        }
+        if !arg_tokens.is_empty() {
+            args.push(self.expr_parser(arg_tokens).parse()?);
+        }
+        return Ok(args);
+    }
+
+    // rejects conditions that are constants of a type other than BOOL
+    fn check_condition(&self, condition: &Expr, line: usize, kind: &str) -> Result<(), Error> {
+        match condition {
+            Expr::Value(RickrollObject::Bool(_))
+            | Expr::Name(_)
+            | Expr::Operation(_, _)
+            | Expr::Call(_, _)
+            | Expr::Conditional(_, _, _) => Ok(()),
+            Expr::Value(RickrollObject::Int(_)) if self.int_conditions => Ok(()),
+            Expr::Value(_) => Err(Error::new(
+                ErrorType::SyntaxError,
+                &format!("{} condition is not boolean", kind)[..],
+                Some(line),
+            )),
+        }
+    }
+
     fn parse_loop(&mut self, line: usize) -> Result<ASTNode, Error> {
-        self.scope.push(Context::new());
+        // the condition is evaluated outside of the body's context
         let condition = self.parse_expr()?;
+        self.scope.push(Context::new());
         let mut body: Vec<ASTNode> = Vec::new();
+        // statements after an else, if there is one
+        let mut else_body: Option<Vec<ASTNode>> = None;
         while !self.tokens.is_empty() {
             let top = self.tokens.front().unwrap();
             if let Token::Statement(ln, kw) = top {
                 match &kw[..] {
                     "WHILE_END" => {
+                        if else_body.is_some() {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                "Else in while loop",
+                                Some(*ln),
+                            ));
+                        }
+                        self.check_condition(&condition, line, "While")?;
                         self.scope.pop();
                         self.tokens.pop_front();
                         return Ok(ASTNode::While(line, condition, body));
                     }
                     "IF_END" => {
+                        self.check_condition(&condition, line, "If")?;
+                        self.scope.pop();
+                        self.tokens.pop_front();
+                        return Ok(ASTNode::If(line, condition, body, else_body.unwrap_or_default()));
+                    }
+                    "ELSE" => {
+                        if else_body.is_some() {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                "Multiple else branches",
+                                Some(*ln),
+                            ));
+                        }
+                        // the else branch gets a fresh context
                         self.scope.pop();
+                        self.scope.push(Context::new());
                         self.tokens.pop_front();
-                        return Ok(ASTNode::If(line, condition, body));
+                        else_body = Some(Vec::new());
                     }
                     "VERSE" => {
                         return Err(Error::new(
@@ -100,20 +293,230 @@ impl Parser {
                         ));
                     }
                     _ => {
-                        body.push(self.parse_statement()?);
+                        let statement = self.parse_statement()?;
+                        match &mut else_body {
+                            Some(else_body) => else_body.push(statement),
+                            None => body.push(statement),
+                        }
                     }
                 }
             } else {
                 panic!("Parser::parse_loop called with invalid statement");
             }
         }
+        return Err(self.unclosed_block());
+    }
+
+    fn parse_do(&mut self, line: usize) -> Result<ASTNode, Error> {
+        self.scope.push(Context::new());
+        let mut body: Vec<ASTNode> = Vec::new();
+        while !self.tokens.is_empty() {
+            let top = self.tokens.front().unwrap();
+            if let Token::Statement(ln, kw) = top {
+                let ln = *ln;
+                match &kw[..] {
+                    "DO_END" => {
+                        // the body's variables can't be used in the condition
+                        self.scope.pop();
+                        self.tokens.pop_front();
+                        let condition = self.parse_expr()?;
+                        self.check_condition(&condition, ln, "Do-while")?;
+                        return Ok(ASTNode::DoWhile(line, ln, condition, body));
+                    }
+                    "WHILE_END" | "IF_END" | "VERSE" => {
+                        return Err(Error::new(
+                            ErrorType::SyntaxError,
+                            "Unbalanced statements",
+                            Some(ln),
+                        ));
+                    }
+                    _ => body.push(self.parse_statement()?),
+                }
+            } else {
+                panic!("Parser::parse_do called with invalid statement");
+            }
+        }
+        return Err(self.unclosed_block());
+    }
+
+    // the constant compared against in a case, which may be negated
+    fn case_pattern(&self, pattern: Expr, line: usize) -> Result<RickrollObject, Error> {
+        match pattern {
+            Expr::Value(obj) => return Ok(obj),
+            Expr::Operation(Operator::UnaryMinus, args) if args.len() == 1 => match &args[0] {
+                Expr::Value(RickrollObject::Int(x)) => return Ok(RickrollObject::Int(x.wrapping_neg())),
+                Expr::Value(RickrollObject::Float(x)) => return Ok(RickrollObject::Float(-x)),
+                _ => (),
+            },
+            _ => (),
+        }
         return Err(Error::new(
             ErrorType::SyntaxError,
-            "Unbalanced statements",
-            None,
+            "Case is not a constant",
+            Some(line),
         ));
     }
 
+    fn parse_match(&mut self, line: usize) -> Result<ASTNode, Error> {
+        let subject = self.parse_expr()?;
+        let mut cases: Vec<(RickrollObject, Vec<ASTNode>)> = Vec::new();
+        let mut default: Option<Vec<ASTNode>> = None;
+        while !self.tokens.is_empty() {
+            let top = self.tokens.front().unwrap();
+            if let Token::Statement(ln, kw) = top {
+                let ln = *ln;
+                match &kw[..] {
+                    "CASE" => {
+                        if default.is_some() {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                "Case after default",
+                                Some(ln),
+                            ));
+                        }
+                        self.tokens.pop_front();
+                        let pattern = self.parse_expr()?;
+                        let pattern = self.case_pattern(pattern, ln)?;
+                        if cases.iter().any(|(x, _)| *x == pattern) {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                &format!("Duplicate case {}", pattern)[..],
+                                Some(ln),
+                            ));
+                        }
+                        // each case gets a fresh context
+                        if !cases.is_empty() {
+                            self.scope.pop();
+                        }
+                        self.scope.push(Context::new());
+                        cases.push((pattern, Vec::new()));
+                    }
+                    "DEFAULT" => {
+                        if default.is_some() {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                "Multiple default cases",
+                                Some(ln),
+                            ));
+                        }
+                        self.tokens.pop_front();
+                        if !cases.is_empty() {
+                            self.scope.pop();
+                        }
+                        self.scope.push(Context::new());
+                        default = Some(Vec::new());
+                    }
+                    "IF_END" => {
+                        if !cases.is_empty() || default.is_some() {
+                            self.scope.pop();
+                        }
+                        self.tokens.pop_front();
+                        return Ok(ASTNode::Match(line, subject, cases, default.unwrap_or_default()));
+                    }
+                    "WHILE_END" | "ELSE" | "VERSE" => {
+                        return Err(Error::new(
+                            ErrorType::SyntaxError,
+                            "Unbalanced statements",
+                            Some(ln),
+                        ));
+                    }
+                    _ => {
+                        if cases.is_empty() && default.is_none() {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                "Statement before the first case",
+                                Some(ln),
+                            ));
+                        }
+                        let statement = self.parse_statement()?;
+                        match &mut default {
+                            Some(default) => default.push(statement),
+                            None => cases.last_mut().unwrap().1.push(statement),
+                        }
+                    }
+                }
+            } else {
+                panic!("Parser::parse_match called with invalid statement");
+            }
+        }
+        return Err(self.unclosed_block());
+    }
+
+    // checks that a new variable can be declared with this name
+    fn check_declaration(&self, name: &str, line: usize) -> Result<(), Error> {
+        if from_word_operator(name).is_some() {
+            return Err(Error::new(
+                ErrorType::NameError,
+                &format!("Variable name {} is reserved", name)[..],
+                Some(line),
+            ));
+        }
+        if self.params.contains(name) {
+            return Err(Error::new(
+                ErrorType::NameError,
+                &format!("Variable name {} shadows a function parameter", name)[..],
+                Some(line),
+            ));
+        }
+        if self.scope.has_var(String::from(name)) {
+            return Err(Error::new(
+                ErrorType::NameError,
+                &format!("Variable name {} already exists", name)[..],
+                Some(line),
+            ));
+        }
+        return Ok(());
+    }
+
+    fn parse_for(&mut self, line: usize) -> Result<ASTNode, Error> {
+        let name = self.get_name();
+        self.check_declaration(&name, line)?;
+        // the bounds are separated by "to"
+        let mut start_tokens: Vec<Token> = Vec::new();
+        while let Some(token) = self.tokens.pop_front() {
+            if let Token::Punc(_, punc) = &token {
+                if punc == "to" {
+                    break;
+                }
+            }
+            start_tokens.push(token);
+        }
+        let start = self.expr_parser(start_tokens).parse()?;
+        let end = self.parse_expr()?;
+        // the loop variable is only visible in the body
+        // like at runtime, it has a context of its own outside of the body's
+        self.scope.push(Context::new());
+        self.scope.add_var(name.clone());
+        self.scope.push(Context::new());
+        let mut body: Vec<ASTNode> = Vec::new();
+        while !self.tokens.is_empty() {
+            let top = self.tokens.front().unwrap();
+            if let Token::Statement(ln, kw) = top {
+                match &kw[..] {
+                    "WHILE_END" => {
+                        self.scope.pop();
+                        self.scope.pop();
+                        self.tokens.pop_front();
+                        return Ok(ASTNode::For(line, name, start, end, body));
+                    }
+                    "IF_END" | "ELSE" | "VERSE" => {
+                        return Err(Error::new(
+                            ErrorType::SyntaxError,
+                            "Unbalanced statements",
+                            Some(*ln),
+                        ));
+                    }
+                    _ => {
+                        body.push(self.parse_statement()?);
+                    }
+                }
+            } else {
+                panic!("Parser::parse_for called with invalid statement");
+            }
+        }
+        return Err(self.unclosed_block());
+    }
+
     fn parse_statement(&mut self) -> Result<ASTNode, Error> {
         let token = self.tokens.pop_front().unwrap();
         if let Token::Statement(line, kw) = token {
@@ -123,17 +526,25 @@ impl Parser {
                 }
                 "LET" => {
                     let name = self.get_name();
-                    if self.scope.has_var(name.clone()) {
+                    self.check_declaration(&name, line)?;
+                    self.scope.add_var(name.clone());
+                    return Ok(ASTNode::Let(line, name));
+                }
+                "ASSIGN" => {
+                    let name = self.get_name();
+                    if !self.scope.has_var(name.clone()) {
                         return Err(Error::new(
                             ErrorType::NameError,
-                            &format!("Variable name {} already exists", name)[..],
+                            &format!("Variable name {} doesn't exist", name)[..],
                             Some(line),
                         ));
                     }
-                    self.scope.add_var(name.clone());
-                    return Ok(ASTNode::Let(line, name));
+                    let var = self.var(name);
+                    let expr = self.parse_expr()?;
+                    self.check_initializer(&var.name, &expr, line)?;
+                    return Ok(ASTNode::Assign(line, var, expr));
                 }
-                "ASSIGN" => {
+                "COMPOUND" => {
                     let name = self.get_name();
                     if !self.scope.has_var(name.clone()) {
                         return Err(Error::new(
@@ -142,63 +553,186 @@ impl Parser {
                             Some(line),
                         ));
                     }
-                    return Ok(ASTNode::Assign(line, name, self.parse_expr()?));
+                    let op = match self.tokens.pop_front() {
+                        Some(Token::Operator(_, op)) => op,
+                        _ => panic!("Parser::parse_statement called with malformed compound assignment"),
+                    };
+                    // the variable is the first operand, which comes last
+                    let var = self.var(name);
+                    let expr = Expr::Operation(op, vec![self.parse_expr()?, Expr::Name(var.clone())]);
+                    self.check_initializer(&var.name, &expr, line)?;
+                    return Ok(ASTNode::Assign(line, var, expr));
+                }
+                "INCREMENT" | "DECREMENT" => {
+                    let name = self.get_name();
+                    if !self.scope.has_var(name.clone()) {
+                        return Err(Error::new(
+                            ErrorType::NameError,
+                            &format!("Variable name {} doesn't exist", name)[..],
+                            Some(line),
+                        ));
+                    }
+                    let step = if kw == "INCREMENT" { 1 } else { -1 };
+                    return Ok(ASTNode::Step(line, self.var(name), step));
+                }
+                "ASSIGN_INDEX" => {
+                    let name = self.get_name();
+                    if !self.scope.has_var(name.clone()) {
+                        return Err(Error::new(
+                            ErrorType::NameError,
+                            &format!("Variable name {} doesn't exist", name)[..],
+                            Some(line),
+                        ));
+                    }
+                    // each index is the single token after a ":"
+                    let mut indices: Vec<Expr> = Vec::new();
+                    while let Some(Token::Punc(_, punc)) = self.tokens.front() {
+                        if punc != ":" {
+                            break;
+                        }
+                        self.tokens.pop_front();
+                        let index_token = self.tokens.pop_front().unwrap();
+                        indices.push(self.expr_parser(vec![index_token]).parse()?);
+                    }
+                    let var = self.var(name);
+                    return Ok(ASTNode::AssignIndex(line, var, indices, self.parse_expr()?));
                 }
                 "CHECK" => {
                     return self.parse_loop(line);
                 }
-                "WHILE_END" | "IF_END" => {
+                "FOR" => {
+                    return self.parse_for(line);
+                }
+                "MATCH" => {
+                    return self.parse_match(line);
+                }
+                "DO" => {
+                    return self.parse_do(line);
+                }
+                "CASE" | "DEFAULT" => {
+                    return Err(Error::new(
+                        ErrorType::SyntaxError,
+                        "Case without match",
+                        Some(line),
+                    ));
+                }
+                "WHILE_END" | "IF_END" | "DO_END" => {
                     return Err(Error::new(
                         ErrorType::SyntaxError,
                         "Unbalanced statements",
                         Some(line),
                     ));
                 }
+                "ELSE" => {
+                    return Err(Error::new(
+                        ErrorType::SyntaxError,
+                        "Else without if",
+                        Some(line),
+                    ));
+                }
                 "RUN" => {
                     let name = self.get_name();
-                    if !self.func_cache.contains(&name) && !BUILTIN_FUNCTIONS.contains_key(&name) {
+                    if !self.function_exists(&name) {
                         return Err(Error::new(
                             ErrorType::NameError,
                             &format!("Function name {} doesn't exist", name)[..],
                             Some(line),
                         ));
                     }
-                    let mut args: Vec<String> = Vec::new();
-                    while !self.tokens.is_empty() {
-                        match self.tokens.front().unwrap() {
-                            Token::Name(_, name) => {
-                                args.push(name.clone());
-                                self.tokens.pop_front();
-                            }
-                            _ => break,
-                        }
+                    let args = self.parse_args()?;
+                    // user functions shadow builtins
+                    if !self.func_cache.contains(&name) {
+                        self.builtins.check_arity(&name, args.len(), Some(line))?;
                     }
                     return Ok(ASTNode::Run(line, name, args));
                 }
                 "RUN_ASSIGN" => {
-                    let var_name = self.get_name();
+                    // several variables are separated by ","
+                    let mut var_names = vec![self.get_name()];
+                    while let Some(Token::Punc(_, punc)) = self.tokens.front() {
+                        if punc != "," {
+                            break;
+                        }
+                        self.tokens.pop_front();
+                        var_names.push(self.get_name());
+                    }
                     let name = self.get_name();
-                    if !self.func_cache.contains(&name) && !BUILTIN_FUNCTIONS.contains_key(&name) {
+                    if !self.function_exists(&name) {
                         return Err(Error::new(
                             ErrorType::NameError,
                             &format!("Function name {} doesn't exist", name)[..],
                             Some(line),
                         ));
                     }
-                    let mut args: Vec<String> = Vec::new();
-                    while !self.tokens.is_empty() {
-                        match self.tokens.front().unwrap() {
-                            Token::Name(_, name) => {
-                                args.push(name.clone());
-                                self.tokens.pop_front();
+                    let args = self.parse_args()?;
+                    // user functions shadow builtins
+                    if !self.func_cache.contains(&name) {
+                        self.builtins.check_arity(&name, args.len(), Some(line))?;
+                    }
+                    if self.in_global_block {
+                        for var in &var_names {
+                            if self.scope.is_global(var.clone()) {
+                                self.initialized.insert(var.clone());
                             }
-                            _ => break,
                         }
                     }
-                    return Ok(ASTNode::RunAssign(line, var_name, name, args));
+                    return Ok(ASTNode::RunAssign(line, var_names, name, args));
                 }
                 "RETURN" => {
-                    return Ok(ASTNode::Return(line, self.parse_expr()?));
+                    return Ok(ASTNode::Return(line, self.parse_args()?));
+                }
+                "BREAK" => {
+                    let label = match self.tokens.front() {
+                        Some(Token::Name(_, _)) => Some(self.get_name()),
+                        _ => None,
+                    };
+                    if let Some(label) = &label {
+                        if !self.labels.contains(label) {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                &format!("No loop labelled {}", label)[..],
+                                Some(line),
+                            ));
+                        }
+                    }
+                    return Ok(ASTNode::Break(line, label));
+                }
+                "LABEL" => {
+                    let label = self.get_name();
+                    if self.labels.contains(&label) {
+                        return Err(Error::new(
+                            ErrorType::SyntaxError,
+                            &format!("Label {} is already in use", label)[..],
+                            Some(line),
+                        ));
+                    }
+                    // the labelled statement has to be on the same line
+                    match self.tokens.front() {
+                        Some(Token::Statement(ln, _)) if *ln == line => (),
+                        _ => {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                "Only loops can be labelled",
+                                Some(line),
+                            ))
+                        }
+                    }
+                    self.labels.push(label.clone());
+                    let res = self.parse_statement();
+                    self.labels.pop();
+                    let res = res?;
+                    match res {
+                        ASTNode::While(..) | ASTNode::For(..) | ASTNode::DoWhile(..) => {
+                            return Ok(ASTNode::Labelled(line, label, Box::new(res)))
+                        }
+                        _ => {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                "Only loops can be labelled",
+                                Some(line),
+                            ))
+                        }
+                    }
                 }
                 _ => panic!("Parser::parse_statement called with invalid keyword {}", kw),
             }
@@ -215,15 +749,20 @@ impl Parser {
         let token = self.tokens.pop_front().unwrap();
         let mut body: Vec<ASTNode> = Vec::new();
         if let Token::Statement(ln, kw) = &token {
-            if kw.clone() == String::from("VERSE") {
-                // add scope
-                self.scope.push(Context::new());
+            if kw == "VERSE" {
                 // extract name
                 let name_token = self.tokens.pop_front().unwrap();
                 let name = match name_token {
                     Token::Name(_, name) => name,
                     _ => panic!("Parser::parse_function called with malformed verse token"),
                 };
+                // add scope
+                // intro declarations are globals, so they stay in the global context
+                let is_intro = name == "[INTRO]";
+                if !is_intro {
+                    self.scope.push(Context::new());
+                }
+                self.in_global_block = is_intro;
                 // insert into func_cache
                 if self.func_cache.contains(&name) {
                     return Err(Error::new(
@@ -235,13 +774,59 @@ impl Parser {
                 self.func_cache.insert(name.clone());
                 // extract arguments
                 let mut args: Vec<String> = Vec::new();
+                let mut defaults: Vec<Expr> = Vec::new();
+                self.params.clear();
                 while !self.tokens.is_empty() {
                     let front = self.tokens.front().unwrap();
                     match front {
-                        Token::Name(_, name) => {
-                            args.push(name.clone());
-                            self.scope.add_var(name.clone());
+                        Token::Name(ln, name) => {
+                            let ln = *ln;
+                            let name = name.clone();
+                            if from_word_operator(&name).is_some() {
+                                return Err(Error::new(
+                                    ErrorType::NameError,
+                                    &format!("Variable name {} is reserved", name)[..],
+                                    Some(ln),
+                                ));
+                            }
                             self.tokens.pop_front();
+                            let defaults_before = defaults.len();
+                            // a default is an expression after "=", up to the next ","
+                            if let Some(Token::Punc(_, punc)) = self.tokens.front() {
+                                if punc == "=" {
+                                    self.tokens.pop_front();
+                                    let mut default_tokens: Vec<Token> = Vec::new();
+                                    let mut depth = 0;
+                                    while let Some(token) = self.tokens.front() {
+                                        match token {
+                                            Token::Statement(_, _) => break,
+                                            Token::Punc(_, punc) if punc == "," && depth == 0 => break,
+                                            Token::Punc(_, punc) if punc == "(" => depth += 1,
+                                            Token::Punc(_, punc) if punc == ")" => depth -= 1,
+                                            _ => (),
+                                        }
+                                        default_tokens.push(self.tokens.pop_front().unwrap());
+                                    }
+                                    // earlier parameters can be used in a default
+                                    defaults.push(self.expr_parser(default_tokens).parse()?);
+                                }
+                            }
+                            // parameters after one with a default need defaults too
+                            if defaults_before > 0 && defaults.len() == defaults_before {
+                                return Err(Error::new(
+                                    ErrorType::SyntaxError,
+                                    &format!("Parameter {} needs a default value", name)[..],
+                                    Some(ln),
+                                ));
+                            }
+                            args.push(name.clone());
+                            self.params.insert(name.clone());
+                            self.scope.add_var(name);
+                            if let Some(Token::Punc(_, punc)) = self.tokens.front() {
+                                if punc == "," {
+                                    self.tokens.pop_front();
+                                }
+                            }
                         }
                         _ => break,
                     }
@@ -250,7 +835,7 @@ impl Parser {
                 while !self.tokens.is_empty() {
                     let front = self.tokens.front().unwrap();
                     if let Token::Statement(_, kw) = front {
-                        if String::from(kw) != String::from("VERSE") {
+                        if kw != "VERSE" {
                             body.push(self.parse_statement()?);
                         } else {
                             break;
@@ -263,8 +848,12 @@ impl Parser {
                         ));
                     }
                 }
-                self.scope.pop();
-                return Ok(ASTNode::Function(*ln, name, args, body));
+                if !is_intro {
+                    self.scope.pop();
+                }
+                self.in_global_block = false;
+                check_breaks(&body)?;
+                return Ok(ASTNode::Function(*ln, name, args, defaults, body));
             } else {
                 return Err(Error::new(
                     ErrorType::SyntaxError,
@@ -281,11 +870,77 @@ impl Parser {
         }
     }
 
+    // parses one input of an interactive session, made of verses or statements
+    // statements are run in the global context, like in an intro block
+    // returns the verses and statements, or None if a block is still open at the end
+    // nothing is declared if the input can't be parsed
+    pub fn parse_input(&mut self, tokens: Vec<Token>) -> Result<Option<ParsedInput>, Error> {
+        let scope = self.scope.clone();
+        let func_cache = self.func_cache.clone();
+        let initialized = self.initialized.clone();
+        self.incomplete = false;
+        self.tokens = VecDeque::from(tokens);
+        let res = self.parse_input_tokens();
+        self.params.clear();
+        self.in_global_block = false;
+        match res {
+            Ok(parsed) => return Ok(Some(parsed)),
+            Err(err) => {
+                self.scope = scope;
+                self.func_cache = func_cache;
+                self.initialized = initialized;
+                // the block may be closed by the next lines
+                if self.is_incomplete() {
+                    return Ok(None);
+                }
+                self.tokens.clear();
+                return Err(err);
+            }
+        }
+    }
+
+    fn parse_input_tokens(&mut self) -> Result<ParsedInput, Error> {
+        let mut functions: Vec<ASTNode> = Vec::new();
+        let mut statements: Vec<ASTNode> = Vec::new();
+        while let Some(token) = self.tokens.front() {
+            match token {
+                Token::Statement(ln, kw) if kw == "VERSE" => {
+                    if let Some(Token::Name(_, name)) = self.tokens.get(1) {
+                        if name == "[INTRO]" || name == "[CHORUS]" {
+                            return Err(Error::new(
+                                ErrorType::SyntaxError,
+                                "Only verses can be defined in an interactive session",
+                                Some(*ln),
+                            ));
+                        }
+                    }
+                    functions.push(self.parse_function()?);
+                    self.params.clear();
+                }
+                Token::Statement(_, _) => {
+                    self.in_global_block = true;
+                    let statement = self.parse_statement()?;
+                    self.in_global_block = false;
+                    check_breaks(std::slice::from_ref(&statement))?;
+                    statements.push(statement);
+                }
+                _ => {
+                    return Err(Error::new(
+                        ErrorType::SyntaxError,
+                        "Illegal statement",
+                        Some(token.get_line()),
+                    ))
+                }
+            }
+        }
+        return Ok((functions, statements));
+    }
+
     pub fn parse(mut self) -> Result<HashMap<String, ASTNode>, Error> {
         while !self.tokens.is_empty() {
             // parse function
             let fnc = self.parse_function()?;
-            if let ASTNode::Function(_, name, _, _) = &fnc {
+            if let ASTNode::Function(_, name, _, _, _) = &fnc {
                 self.output.insert(name.clone(), fnc);
             } else {
                 return Err(Error::new(
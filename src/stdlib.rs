@@ -3,26 +3,156 @@ use crate::error::*;
 
 use lazy_static::lazy_static;
 
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
 use std::io::{BufRead, Write};
 use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
 
-type LibFunction = fn(Vec<RickrollObject>, &mut dyn Write, &mut dyn BufRead) -> Result<RickrollObject, Error>;
+pub type LibFunction = fn(Vec<RickrollObject>, &mut dyn Write, &mut dyn BufRead) -> Result<RickrollObject, Error>;
+
+thread_local! {
+    // state of the xorshift generator used by Random, seeded randomly by the standard library
+    static RANDOM_STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+// a builtin along with the number of arguments it takes
+#[derive(Debug, Clone, Copy)]
+pub struct Builtin {
+    pub function: LibFunction,
+    pub arity: Option<usize>, // None if it takes any number of arguments
+    pub optional_args: usize, // number of arguments that may be left out at the end
+}
+
+impl Builtin {
+    pub fn new(function: LibFunction, arity: usize) -> Builtin {
+        Builtin {
+            function,
+            arity: Some(arity),
+            optional_args: 0,
+        }
+    }
+
+    // a builtin taking any number of arguments
+    pub fn variadic(function: LibFunction) -> Builtin {
+        Builtin {
+            function,
+            arity: None,
+            optional_args: 0,
+        }
+    }
+
+    pub fn with_optional_args(mut self, optional_args: usize) -> Builtin {
+        self.optional_args = optional_args;
+        self
+    }
+
+    // checks the number of arguments passed to the builtin, before the program runs
+    pub fn check_arity(&self, name: &str, count: usize, line: Option<usize>) -> Result<(), Error> {
+        if let Some(expected) = self.arity {
+            let optional = self.optional_args;
+            if count < expected || count > expected + optional {
+                let range = if optional == 0 {
+                    expected.to_string()
+                } else {
+                    format!("{} to {}", expected, expected + optional)
+                };
+                return Err(Error::new(
+                    ErrorType::SyntaxError,
+                    &format!("Wrong number of arguments for {}, expected {} but found {}", name, range, count)[..],
+                    line,
+                ));
+            }
+        }
+        return Ok(());
+    }
+}
+
+// native functions added by an embedder, shared by the Parser and the Interpreter
+// they replace builtins of the same name, but user functions shadow them
+#[derive(Debug, Clone, Default)]
+pub struct Builtins {
+    functions: HashMap<String, Builtin>,
+}
+
+impl Builtins {
+    pub fn new() -> Builtins {
+        Builtins::default()
+    }
+
+    pub fn with_function(mut self, name: &str, builtin: Builtin) -> Builtins {
+        self.functions.insert(String::from(name), builtin);
+        self
+    }
+
+    // the custom builtin with the name, None if there isn't one
+    pub fn get(&self, name: &str) -> Option<Builtin> {
+        return self.functions.get(name).copied();
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        return self.functions.contains_key(name);
+    }
+
+    // checks the number of arguments passed to a custom or standard builtin
+    pub fn check_arity(&self, name: &str, count: usize, line: Option<usize>) -> Result<(), Error> {
+        match self.functions.get(name) {
+            Some(builtin) => return builtin.check_arity(name, count, line),
+            None => return check_arity(name, count, line),
+        }
+    }
+}
 
 lazy_static! {
-    pub static ref BUILTIN_FUNCTIONS: HashMap<String, LibFunction> = {
+    pub static ref BUILTIN_FUNCTIONS: HashMap<String, Builtin> = {
         let mut m = HashMap::new();
-        m.insert(String::from("ArrayOf"), array_of as LibFunction);
-        m.insert(String::from("ArrayPop"), array_pop as LibFunction);
-        m.insert(String::from("ArrayPush"), array_push as LibFunction);
-        m.insert(String::from("ArrayReplace"), array_replace as LibFunction);
-        m.insert(String::from("ArrayLength"), array_length as LibFunction);
-        m.insert(String::from("PutChar"), put_char as LibFunction);
-        m.insert(String::from("ReadLine"), read_line as LibFunction);
+        m.insert(String::from("ArrayOf"), Builtin::variadic(array_of));
+        m.insert(String::from("ArrayPop"), Builtin::new(array_pop, 2));
+        m.insert(String::from("ArrayPush"), Builtin::new(array_push, 3));
+        m.insert(String::from("ArrayReplace"), Builtin::new(array_replace, 3));
+        m.insert(String::from("ArrayLength"), Builtin::new(array_length, 1));
+        m.insert(String::from("IsEmpty"), Builtin::new(is_empty, 1));
+        m.insert(String::from("ArraySlice"), Builtin::new(array_slice, 3));
+        m.insert(String::from("ArrayConcat"), Builtin::new(array_concat, 2));
+        m.insert(String::from("ArrayReverse"), Builtin::new(array_reverse, 1));
+        m.insert(String::from("ArrayFill"), Builtin::new(array_fill, 2));
+        m.insert(String::from("ArrayContains"), Builtin::new(array_contains, 2));
+        m.insert(String::from("PutChar"), Builtin::new(put_char, 1));
+        m.insert(String::from("ReadLine"), Builtin::new(read_line, 0));
+        m.insert(String::from("ReadChar"), Builtin::new(read_char, 0));
+        m.insert(String::from("PeekChar"), Builtin::new(peek_char, 0));
+        m.insert(String::from("Sleep"), Builtin::new(sleep, 1));
+        m.insert(String::from("Exit"), Builtin::new(exit, 1));
+        m.insert(String::from("Assert"), Builtin::new(assert, 1).with_optional_args(1));
+        m.insert(String::from("Between"), Builtin::new(between, 3));
+        m.insert(String::from("Abs"), Builtin::new(abs, 1));
+        m.insert(String::from("Sqrt"), Builtin::new(sqrt, 1));
+        m.insert(String::from("Pow"), Builtin::new(pow, 2));
+        m.insert(String::from("Min"), Builtin::new(min, 2));
+        m.insert(String::from("Max"), Builtin::new(max, 2));
+        m.insert(String::from("Floor"), Builtin::new(floor, 1));
+        m.insert(String::from("Ceil"), Builtin::new(ceil, 1));
+        m.insert(String::from("Round"), Builtin::new(round, 1));
+        m.insert(String::from("Random"), Builtin::new(random, 2));
+        m.insert(String::from("CharToInt"), Builtin::new(char_to_int, 1));
+        m.insert(String::from("IntToChar"), Builtin::new(int_to_char, 1));
+        m.insert(String::from("ToString"), Builtin::new(to_string, 1));
         m
     };
 }
 
+// checks the number of arguments passed to a builtin, before the program runs
+pub fn check_arity(name: &str, count: usize, line: Option<usize>) -> Result<(), Error> {
+    if let Some(builtin) = BUILTIN_FUNCTIONS.get(name) {
+        builtin.check_arity(name, count, line)?;
+    }
+    return Ok(());
+}
+
 fn array_of(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
     return Ok(RickrollObject::Array(Rc::new(args)));
 }
@@ -36,11 +166,11 @@ fn array_pop(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead)
     if let RickrollObject::Array(x) = arr {
         let mut x = (*x).clone();
         if let RickrollObject::Int(y) = idx {
-            if y >= 0 && (y as usize) < x.len() {
-                x.remove(y as usize);
+            if let Some(ind) = resolve_index(y, x.len()) {
+                x.remove(ind);
                 return Ok(RickrollObject::Array(Rc::new(x)));
             }  else {
-                return Err(Error::new(ErrorType::RuntimeError, "Array Index out of Bounds", None));
+                return Err(Error::new(ErrorType::IndexOutOfBoundsError, "Array index out of bounds", None));
             }
         }
     }
@@ -61,7 +191,7 @@ fn array_push(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead)
                 x.insert(y as usize, val);
                 return Ok(RickrollObject::Array(Rc::new(x)));
             } else {
-                return Err(Error::new(ErrorType::RuntimeError, "Array Index out of Bounds", None));
+                return Err(Error::new(ErrorType::IndexOutOfBoundsError, "Array index out of bounds", None));
             }
         }
     }
@@ -78,11 +208,11 @@ fn array_replace(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRe
     if let RickrollObject::Array(x) = arr {
         if let RickrollObject::Int(y) = idx {
             let mut x = (*x).clone();
-            if y >= 0 && (y as usize) < x.len() {
-                x[y as usize] = val;
+            if let Some(ind) = resolve_index(y, x.len()) {
+                x[ind] = val;
                 return Ok(RickrollObject::Array(Rc::new(x)));
             } else {
-                return Err(Error::new(ErrorType::RuntimeError, "Array Index out of Bounds", None));
+                return Err(Error::new(ErrorType::IndexOutOfBoundsError, "Array index out of bounds", None));
             }
         }
     }
@@ -100,6 +230,80 @@ fn array_length(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRea
     return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for ArrayLength", None));
 }
 
+fn array_slice(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 3 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for ArraySlice", None));
+    }
+    if let (RickrollObject::Array(x), RickrollObject::Int(start), RickrollObject::Int(end)) =
+        (args[0].clone(), args[1].clone(), args[2].clone())
+    {
+        if start < 0 || start > end || (end as usize) > x.len() {
+            return Err(Error::new(ErrorType::IndexOutOfBoundsError, "Array slice out of bounds", None));
+        }
+        return Ok(RickrollObject::Array(Rc::new(x[(start as usize)..(end as usize)].to_vec())));
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for ArraySlice", None));
+}
+
+fn array_concat(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 2 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for ArrayConcat", None));
+    }
+    if let (RickrollObject::Array(x), RickrollObject::Array(y)) = (args[0].clone(), args[1].clone()) {
+        let mut x = (*x).clone();
+        x.extend(y.iter().cloned());
+        return Ok(RickrollObject::Array(Rc::new(x)));
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for ArrayConcat", None));
+}
+
+fn array_reverse(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 1 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for ArrayReverse", None));
+    }
+    if let RickrollObject::Array(x) = args[0].clone() {
+        let mut x = (*x).clone();
+        x.reverse();
+        return Ok(RickrollObject::Array(Rc::new(x)));
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for ArrayReverse", None));
+}
+
+fn array_contains(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 2 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for ArrayContains", None));
+    }
+    if let RickrollObject::Array(x) = args[0].clone() {
+        // same as the in operator
+        return Ok(RickrollObject::Bool(x.contains(&args[1])));
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for ArrayContains", None));
+}
+
+fn array_fill(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 2 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for ArrayFill", None));
+    }
+    if let RickrollObject::Int(count) = args[1] {
+        if count < 0 {
+            return Err(Error::new(ErrorType::RuntimeError, "Negative length for ArrayFill", None));
+        }
+        return Ok(RickrollObject::Array(Rc::new(vec![args[0].clone(); count as usize])));
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for ArrayFill", None));
+}
+
+fn is_empty(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 1 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for IsEmpty", None));
+    }
+    let arr = args[0].clone();
+    if let RickrollObject::Array(x) = arr {
+        return Ok(RickrollObject::Bool(x.is_empty()));
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for IsEmpty", None));
+}
+
 fn put_char(args: Vec<RickrollObject>, writer: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
     if args.len() != 1 {
         return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for PutChar", None));
@@ -113,11 +317,30 @@ fn put_char(args: Vec<RickrollObject>, writer: &mut dyn Write, _: &mut dyn BufRe
 }
 
 fn read_line(args: Vec<RickrollObject>, _: &mut dyn Write, reader: &mut dyn BufRead) -> Result<RickrollObject, Error> {
-    if args.len() != 0 {
+    return read_line_with(args, reader, false);
+}
+
+// ReadLine replacing invalid UTF-8 with U+FFFD instead of throwing an error
+pub fn read_line_lossy(args: Vec<RickrollObject>, _: &mut dyn Write, reader: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    return read_line_with(args, reader, true);
+}
+
+fn read_line_with(args: Vec<RickrollObject>, reader: &mut dyn BufRead, lossy: bool) -> Result<RickrollObject, Error> {
+    if !args.is_empty() {
         return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for ReadLine", None));
     }
-    let mut line = String::new();
-    reader.read_line(&mut line).unwrap();
+    let mut bytes = Vec::new();
+    if reader.read_until(b'\n', &mut bytes).is_err() {
+        return Err(Error::new(ErrorType::RuntimeError, "Could not read input", None));
+    }
+    let line = if lossy {
+        String::from_utf8_lossy(&bytes).to_string()
+    } else {
+        match String::from_utf8(bytes) {
+            Ok(x) => x,
+            Err(_) => return Err(Error::new(ErrorType::RuntimeError, "Invalid UTF-8 in input", None)),
+        }
+    };
     let mut arr = Vec::new();
     for c in line.chars() {
         if c == '\n' || c == '\r' {
@@ -127,3 +350,341 @@ fn read_line(args: Vec<RickrollObject>, _: &mut dyn Write, reader: &mut dyn BufR
     }
     return Ok(RickrollObject::Array(Rc::new(arr)));
 }
+
+// reads a single byte, None at the end of input
+fn read_byte(reader: &mut dyn BufRead) -> Result<Option<u8>, Error> {
+    let mut byte = [0u8; 1];
+    match reader.read(&mut byte) {
+        Ok(0) => return Ok(None),
+        Ok(_) => return Ok(Some(byte[0])),
+        Err(_) => return Err(Error::new(ErrorType::RuntimeError, "Could not read input", None)),
+    }
+}
+
+// the leading bits of the first byte give the length of a UTF-8 sequence
+fn utf8_len(first: u8) -> Result<usize, Error> {
+    if first < 0x80 {
+        return Ok(1);
+    } else if first >> 5 == 0b110 {
+        return Ok(2);
+    } else if first >> 4 == 0b1110 {
+        return Ok(3);
+    } else if first >> 3 == 0b11110 {
+        return Ok(4);
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Invalid UTF-8 in input", None));
+}
+
+fn read_char(args: Vec<RickrollObject>, _: &mut dyn Write, reader: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if !args.is_empty() {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for ReadChar", None));
+    }
+    let first = match read_byte(reader)? {
+        Some(x) => x,
+        None => return Ok(RickrollObject::Undefined),
+    };
+    let len = utf8_len(first)?;
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        match read_byte(reader)? {
+            Some(x) => bytes.push(x),
+            None => return Err(Error::new(ErrorType::RuntimeError, "Invalid UTF-8 in input", None)),
+        }
+    }
+    match std::str::from_utf8(&bytes) {
+        Ok(x) => return Ok(RickrollObject::Char(x.chars().next().unwrap())),
+        Err(_) => return Err(Error::new(ErrorType::RuntimeError, "Invalid UTF-8 in input", None)),
+    }
+}
+
+fn peek_char(args: Vec<RickrollObject>, _: &mut dyn Write, reader: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if !args.is_empty() {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for PeekChar", None));
+    }
+    // inspect the buffered input without consuming it
+    let buf = match reader.fill_buf() {
+        Ok(x) => x,
+        Err(_) => return Err(Error::new(ErrorType::RuntimeError, "Could not read input", None)),
+    };
+    if buf.is_empty() {
+        return Ok(RickrollObject::Undefined);
+    }
+    let len = utf8_len(buf[0])?;
+    if buf.len() < len {
+        // the character continues past the buffered input
+        return Err(Error::new(ErrorType::RuntimeError, "Could not peek at input", None));
+    }
+    match std::str::from_utf8(&buf[..len]) {
+        Ok(x) => return Ok(RickrollObject::Char(x.chars().next().unwrap())),
+        Err(_) => return Err(Error::new(ErrorType::RuntimeError, "Invalid UTF-8 in input", None)),
+    }
+}
+
+fn sleep(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 1 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for Sleep", None));
+    }
+    let millis = args[0].clone();
+    if let RickrollObject::Int(x) = millis {
+        if x < 0 {
+            return Err(Error::new(ErrorType::RuntimeError, "Negative duration for Sleep", None));
+        }
+        thread::sleep(Duration::from_millis(x as u64));
+        return Ok(RickrollObject::Undefined);
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for Sleep", None));
+}
+
+// stops the program with a status, which the interpreter catches before it is reported
+fn exit(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 1 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for Exit", None));
+    }
+    if let RickrollObject::Int(code) = args[0] {
+        // the status has to fit in the exit code of the process
+        if !(0..=255).contains(&code) {
+            return Err(Error::new(
+                ErrorType::ValueError,
+                &format!("Exit status {} is not between 0 and 255", code)[..],
+                None,
+            ));
+        }
+        return Err(Error::new(ErrorType::ProgramExit(code), "Program exited", None));
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for Exit", None));
+}
+
+// fails unless the condition is TRUE, with an optional message
+fn assert(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    use RickrollObject::*;
+    if args.is_empty() || args.len() > 2 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for Assert", None));
+    }
+    let message = match args.get(1) {
+        None => None,
+        Some(Array(arr)) if arr.iter().all(|x| matches!(x, Char(_))) => Some(arr.iter().map(|x| x.to_string()).collect::<String>()),
+        Some(_) => return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for Assert", None)),
+    };
+    match (&args[0], message) {
+        (Bool(true), _) => return Ok(Undefined),
+        (Bool(false), None) => return Err(Error::new(ErrorType::RuntimeError, "Assertion failed", None)),
+        (Bool(false), Some(message)) => {
+            return Err(Error::new(
+                ErrorType::RuntimeError,
+                &format!("Assertion failed: {}", message)[..],
+                None,
+            ))
+        }
+        _ => return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for Assert", None)),
+    }
+}
+
+fn between(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    use RickrollObject::*;
+    if args.len() != 3 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for Between", None));
+    }
+    match (args[0].clone(), args[1].clone(), args[2].clone()) {
+        (Int(x), Int(lo), Int(hi)) => return Ok(Bool(lo <= x && x <= hi)),
+        (x, lo, hi) => {
+            // mixed arguments are compared as floats
+            let mut vals = Vec::new();
+            for arg in [x, lo, hi].iter() {
+                match arg {
+                    Int(y) => vals.push(*y as f32),
+                    Float(y) => vals.push(*y),
+                    _ => return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for Between", None)),
+                }
+            }
+            return Ok(Bool(vals[1] <= vals[0] && vals[0] <= vals[2]));
+        }
+    }
+}
+
+// numeric value of an INT or FLOAT as a float
+fn as_float(obj: &RickrollObject) -> Option<f32> {
+    match obj {
+        RickrollObject::Int(x) => Some(*x as f32),
+        RickrollObject::Float(x) => Some(*x),
+        _ => None,
+    }
+}
+
+fn abs(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 1 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for Abs", None));
+    }
+    match args[0].clone() {
+        RickrollObject::Int(x) => return Ok(RickrollObject::Int(x.wrapping_abs())),
+        RickrollObject::Float(x) => return Ok(RickrollObject::Float(x.abs())),
+        _ => return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for Abs", None)),
+    }
+}
+
+fn sqrt(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 1 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for Sqrt", None));
+    }
+    if let Some(x) = as_float(&args[0]) {
+        if x < 0.0 {
+            return Err(Error::new(ErrorType::RuntimeError, "Negative argument for Sqrt", None));
+        }
+        return Ok(RickrollObject::Float(x.sqrt()));
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for Sqrt", None));
+}
+
+fn pow(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    use RickrollObject::*;
+    if args.len() != 2 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for Pow", None));
+    }
+    // same as the ** operator
+    match (args[0].clone(), args[1].clone()) {
+        (Int(x), Int(y)) => {
+            if y < 0 {
+                return Err(Error::new(ErrorType::RuntimeError, "Negative integer exponent", None));
+            }
+            return Ok(Int(x.wrapping_pow(y as u32)));
+        }
+        (x, y) => match (as_float(&x), as_float(&y)) {
+            (Some(x), Some(y)) => return Ok(Float(x.powf(y))),
+            _ => return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for Pow", None)),
+        },
+    }
+}
+
+// shared by Min and Max, which return the second argument if the first compares as replace_if
+fn min_max(args: Vec<RickrollObject>, name: &str, replace_if: Ordering) -> Result<RickrollObject, Error> {
+    use RickrollObject::*;
+    if args.len() != 2 {
+        return Err(Error::new(ErrorType::RuntimeError, &format!("Wrong number of arguments for {}", name)[..], None));
+    }
+    match (args[0].clone(), args[1].clone()) {
+        (Int(x), Int(y)) => return Ok(Int(if x.cmp(&y) == replace_if { y } else { x })),
+        (x, y) => match (as_float(&x), as_float(&y)) {
+            // mixed arguments are converted to floats
+            (Some(x), Some(y)) => return Ok(Float(if x.partial_cmp(&y) == Some(replace_if) { y } else { x })),
+            _ => return Err(Error::new(ErrorType::RuntimeError, &format!("Wrong type of arguments for {}", name)[..], None)),
+        },
+    }
+}
+
+fn min(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    return min_max(args, "Min", Ordering::Greater);
+}
+
+fn max(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    return min_max(args, "Max", Ordering::Less);
+}
+
+// shared by Floor, Ceil and Round, which convert a FLOAT to an INT
+fn to_int(args: Vec<RickrollObject>, name: &str, convert: fn(f32) -> f32) -> Result<RickrollObject, Error> {
+    if args.len() != 1 {
+        return Err(Error::new(ErrorType::RuntimeError, &format!("Wrong number of arguments for {}", name)[..], None));
+    }
+    if let RickrollObject::Float(x) = args[0] {
+        let res = convert(x);
+        // NaN and values outside of the INT range can't be converted
+        if res.is_nan() || res < i32::MIN as f32 || res >= i32::MAX as f32 {
+            return Err(Error::new(ErrorType::IllegalCastError, &format!("Cannot convert {} to INT", x)[..], None));
+        }
+        return Ok(RickrollObject::Int(res as i32));
+    }
+    return Err(Error::new(ErrorType::RuntimeError, &format!("Wrong type of arguments for {}", name)[..], None));
+}
+
+fn floor(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    return to_int(args, "Floor", f32::floor);
+}
+
+fn ceil(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    return to_int(args, "Ceil", f32::ceil);
+}
+
+fn round(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    return to_int(args, "Round", f32::round);
+}
+
+// next number from a xorshift64* generator
+fn next_random() -> u64 {
+    return RANDOM_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545F4914F6CDD1D)
+    });
+}
+
+fn random(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 2 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for Random", None));
+    }
+    if let (RickrollObject::Int(lo), RickrollObject::Int(hi)) = (args[0].clone(), args[1].clone()) {
+        if lo >= hi {
+            return Err(Error::new(ErrorType::RuntimeError, "Empty range for Random", None));
+        }
+        // the bias of taking the remainder is negligible for a range of at most 2^32
+        let range = (hi as i64 - lo as i64) as u64;
+        return Ok(RickrollObject::Int((lo as i64 + (next_random() % range) as i64) as i32));
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for Random", None));
+}
+
+fn char_to_int(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 1 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for CharToInt", None));
+    }
+    if let RickrollObject::Char(x) = args[0] {
+        return Ok(RickrollObject::Int(x as u32 as i32));
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for CharToInt", None));
+}
+
+fn int_to_char(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 1 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for IntToChar", None));
+    }
+    if let RickrollObject::Int(x) = args[0] {
+        return code_point_to_char(x);
+    }
+    return Err(Error::new(ErrorType::RuntimeError, "Wrong type of arguments for IntToChar", None));
+}
+
+fn code_point_to_char(x: i32) -> Result<RickrollObject, Error> {
+    // negative numbers and surrogates aren't code points
+    match if x < 0 { None } else { char::from_u32(x as u32) } {
+        Some(chr) => return Ok(RickrollObject::Char(chr)),
+        None => return Err(Error::new(ErrorType::IllegalCastError, &format!("{} is not a valid character", x)[..], None)),
+    }
+}
+
+// converts a value for the "as" operator, using the same rules as the conversion builtins
+// type_name is INT, FLOAT or CHAR
+pub fn cast(value: RickrollObject, type_name: &str) -> Result<RickrollObject, Error> {
+    use RickrollObject::*;
+    match (value, type_name) {
+        (Int(x), "INT") => return Ok(Int(x)),
+        // floats are truncated towards zero
+        (Float(x), "INT") => return to_int(vec![Float(x)], "as INT", f32::trunc),
+        (Char(x), "INT") => return Ok(Int(x as u32 as i32)),
+        (Bool(x), "INT") => return Ok(Int(x as i32)),
+        (Int(x), "FLOAT") => return Ok(Float(x as f32)),
+        (Float(x), "FLOAT") => return Ok(Float(x)),
+        (Int(x), "CHAR") => return code_point_to_char(x),
+        (Char(x), "CHAR") => return Ok(Char(x)),
+        (value, _) => {
+            return Err(Error::new(ErrorType::IllegalCastError, &format!("Cannot convert {} to {}", value, type_name)[..], None));
+        }
+    }
+}
+
+fn to_string(args: Vec<RickrollObject>, _: &mut dyn Write, _: &mut dyn BufRead) -> Result<RickrollObject, Error> {
+    if args.len() != 1 {
+        return Err(Error::new(ErrorType::RuntimeError, "Wrong number of arguments for ToString", None));
+    }
+    let chars = args[0].to_string().chars().map(RickrollObject::Char).collect();
+    return Ok(RickrollObject::Array(Rc::new(chars)));
+}
@@ -0,0 +1,238 @@
+use crate::error::*;
+use crate::interpreter::Interpreter;
+use crate::lexer::Lexer;
+use crate::lint;
+use crate::parser::Parser;
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/*
+ * A test is a program "NAME.rick" next to a file "NAME.out" holding its
+ * expected output. If "NAME.in" exists, it is used as the standard input.
+ * Errors are part of the output, so failing programs can be tested too.
+ * If "NAME.trace" exists, the program is traced and the trace must match it.
+ * Likewise, "NAME.warn" holds the expected warnings about printing UNDEFINED,
+ * and "NAME.lint" the expected warnings of the lints.
+ */
+
+// memory budget of a test program, so a runaway test fails instead of exhausting memory
+pub const TEST_MAX_MEMORY: usize = 16 * 1024 * 1024;
+
+// outcome of running a single test program
+#[derive(Debug)]
+pub struct TestResult {
+    pub program: PathBuf,
+    // description of the first mismatch, None if the output matched
+    pub failure: Option<String>,
+}
+
+fn read_file(path: &Path) -> Result<String, Error> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text),
+        Err(err) => Err(Error::new(
+            ErrorType::FileError,
+            &format!("Could not read {}: {}", path.display(), err)[..],
+            None,
+        )),
+    }
+}
+
+// output that can still be read after it is handed to the interpreter
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// diagnostics that a test compares besides its output
+#[derive(Clone, Copy)]
+struct Checks {
+    trace: bool,
+    warnings: bool,
+    lints: bool,
+}
+
+// diagnostics collected while running a test, None for those that aren't checked
+struct Diagnostics {
+    trace: Option<String>,
+    warnings: Option<String>,
+    lints: Option<String>,
+}
+
+// reads a file if it exists
+fn read_optional(path: &Path) -> Result<Option<String>, Error> {
+    if path.exists() {
+        return Ok(Some(read_file(path)?));
+    }
+    return Ok(None);
+}
+
+// runs source code with the given input and returns everything it printed
+// along with the diagnostics that are checked
+fn run_capture(src: &str, input: &[u8], checks: Checks, interrupt: &Arc<AtomicBool>) -> (String, Diagnostics) {
+    let mut output: Vec<u8> = Vec::new();
+    let trace_output = SharedBuffer::default();
+    let warn_output = SharedBuffer::default();
+    let mut lints = String::new();
+    let res = Lexer::new(String::from(src))
+        .parse()
+        .and_then(|tokens| Parser::new(tokens).parse())
+        .and_then(|functions| {
+            if checks.lints {
+                for warning in lint::lint(&functions) {
+                    lints += &format!("{}\n", warning);
+                }
+            }
+            let mut interpreter = Interpreter::new(functions)
+                .with_max_memory(Some(TEST_MAX_MEMORY))
+                .with_interrupt(Arc::clone(interrupt));
+            if checks.trace {
+                interpreter = interpreter.with_trace(src, Box::new(trace_output.clone()));
+            }
+            if checks.warnings {
+                interpreter = interpreter.with_undefined_warnings(Box::new(warn_output.clone()));
+            }
+            interpreter.run(&mut output, &mut BufReader::new(input))
+        });
+    let mut printed = String::from_utf8_lossy(&output).to_string();
+    if let Err(err) = res {
+        printed += &format!("{}\n", err);
+    }
+    let read = |buffer: &SharedBuffer| String::from_utf8_lossy(&buffer.0.borrow()).to_string();
+    let diagnostics = Diagnostics {
+        trace: if checks.trace { Some(read(&trace_output)) } else { None },
+        warnings: if checks.warnings { Some(read(&warn_output)) } else { None },
+        lints: if checks.lints { Some(lints) } else { None },
+    };
+    return (printed, diagnostics);
+}
+
+// describes the first line where two outputs differ
+pub fn diff_output(expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    for ind in 0..expected_lines.len().max(actual_lines.len()) {
+        let exp = expected_lines.get(ind);
+        let act = actual_lines.get(ind);
+        if exp != act {
+            return Some(format!(
+                "line {}: expected {}, found {}",
+                ind + 1,
+                exp.map_or(String::from("end of output"), |x| format!("\"{}\"", x)),
+                act.map_or(String::from("end of output"), |x| format!("\"{}\"", x)),
+            ));
+        }
+    }
+    // only trailing newlines differ
+    return Some(String::from("outputs differ in trailing newlines"));
+}
+
+// runs one program against its expected output
+pub fn run_test(program: &Path) -> Result<TestResult, Error> {
+    return run_test_with_interrupt(program, &Arc::new(AtomicBool::new(false)));
+}
+
+// like run_test, but the program fails with an Interrupted error once the flag is set
+pub fn run_test_with_interrupt(program: &Path, interrupt: &Arc<AtomicBool>) -> Result<TestResult, Error> {
+    let src = read_file(program)?;
+    let expected = read_file(&program.with_extension("out"))?;
+    let input_path = program.with_extension("in");
+    // input is read as bytes, since it doesn't need to be valid UTF-8
+    let input = if input_path.exists() {
+        match fs::read(&input_path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                return Err(Error::new(
+                    ErrorType::FileError,
+                    &format!("Could not read {}: {}", input_path.display(), err)[..],
+                    None,
+                ))
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    let expected_trace = read_optional(&program.with_extension("trace"))?;
+    let expected_warnings = read_optional(&program.with_extension("warn"))?;
+    let expected_lints = read_optional(&program.with_extension("lint"))?;
+    let checks = Checks {
+        trace: expected_trace.is_some(),
+        warnings: expected_warnings.is_some(),
+        lints: expected_lints.is_some(),
+    };
+    let (actual, diagnostics) = run_capture(&src, &input, checks, interrupt);
+    let mut failure = diff_output(&expected, &actual);
+    if failure.is_none() {
+        if let (Some(expected), Some(actual)) = (expected_trace, diagnostics.trace) {
+            failure = diff_output(&expected, &actual).map(|msg| format!("trace {}", msg));
+        }
+    }
+    if failure.is_none() {
+        if let (Some(expected), Some(actual)) = (expected_warnings, diagnostics.warnings) {
+            failure = diff_output(&expected, &actual).map(|msg| format!("warnings {}", msg));
+        }
+    }
+    if failure.is_none() {
+        if let (Some(expected), Some(actual)) = (expected_lints, diagnostics.lints) {
+            failure = diff_output(&expected, &actual).map(|msg| format!("lints {}", msg));
+        }
+    }
+    return Ok(TestResult {
+        program: program.to_path_buf(),
+        failure,
+    });
+}
+
+// runs a single test, or every program with an expected output in a directory
+pub fn run_tests(path: &Path) -> Result<Vec<TestResult>, Error> {
+    return run_tests_with_interrupt(path, &Arc::new(AtomicBool::new(false)));
+}
+
+// like run_tests, but stops after the test that is running when the flag is set
+pub fn run_tests_with_interrupt(path: &Path, interrupt: &Arc<AtomicBool>) -> Result<Vec<TestResult>, Error> {
+    if !path.is_dir() {
+        return Ok(vec![run_test_with_interrupt(path, interrupt)?]);
+    }
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            return Err(Error::new(
+                ErrorType::FileError,
+                &format!("Could not read {}: {}", path.display(), err)[..],
+                None,
+            ))
+        }
+    };
+    let mut programs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| {
+            file.extension().is_some_and(|ext| ext == "rick")
+                && file.with_extension("out").exists()
+        })
+        .collect();
+    programs.sort();
+    let mut results = Vec::new();
+    for program in programs {
+        results.push(run_test_with_interrupt(&program, interrupt)?);
+        if interrupt.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+    return Ok(results);
+}
@@ -1,4 +1,3 @@
-use std::collections::HashMap;
 use std::rc::Rc;
 
 // collection of data types
@@ -37,8 +36,40 @@ impl std::fmt::Display for RickrollObject {
     }
 }
 
+impl RickrollObject {
+    // approximate number of bytes used by a value
+    // shared arrays are counted once for every reference to them
+    pub fn size_estimate(&self) -> usize {
+        let size = std::mem::size_of::<RickrollObject>();
+        match self {
+            RickrollObject::Array(arr) => {
+                return size + arr.iter().map(|x| x.size_estimate()).sum::<usize>();
+            }
+            _ => return size,
+        }
+    }
+}
+
+// equality used by the == and != operators
+// values of different types are never equal, so INTs and FLOATs aren't converted
+// arrays are equal if their elements are, and UNDEFINED equals UNDEFINED
+impl PartialEq for RickrollObject {
+    fn eq(&self, other: &RickrollObject) -> bool {
+        use RickrollObject::*;
+        match (self, other) {
+            (Int(x), Int(y)) => x == y,
+            (Float(x), Float(y)) => x == y,
+            (Bool(x), Bool(y)) => x == y,
+            (Char(x), Char(y)) => x == y,
+            (Array(x), Array(y)) => x == y,
+            (Undefined, Undefined) => true,
+            _ => false,
+        }
+    }
+}
+
 // operators
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Operator {
     ArrayAccess,
     Add,
@@ -46,32 +77,80 @@ pub enum Operator {
     Multiply,
     Divide,
     Modulo,
+    Power,
     UnaryMinus,
     And,
     Or,
     Not,
+    BitNot,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
     Greater,
     Less,
     GreaterEquals,
     LessEquals,
     Equals,
     NotEquals,
+    In,
+    CastInt, // postfix "as INT"
+    CastFloat,
+    CastChar,
 }
 
 impl Operator {
+    // checks if operator is right-associative
+    pub fn is_right_assoc(&self) -> bool {
+        matches!(self, Operator::Power)
+    }
+
     // checks if operator is unary
     pub fn is_unary(&self) -> bool {
         use Operator::*;
-        match self {
-            UnaryMinus | Not => true,
-            _ => false,
-        }
+        matches!(self, UnaryMinus | Not | BitNot) || self.is_postfix()
+    }
+
+    // checks if operator comes after its operand
+    pub fn is_postfix(&self) -> bool {
+        use Operator::*;
+        matches!(self, CastInt | CastFloat | CastChar)
+    }
+}
+
+// converts an index into an array of length len, counting negative indices from the end
+// returns None if the index is out of bounds
+pub fn resolve_index(index: i32, len: usize) -> Option<usize> {
+    let index = if index < 0 {
+        len as i64 + index as i64
+    } else {
+        index as i64
+    };
+    if index >= 0 && index < len as i64 {
+        return Some(index as usize);
+    }
+    return None;
+}
+
+// operators written as words, which can't be used as variable names
+pub fn from_word_operator(word: &str) -> Option<&'static str> {
+    match word {
+        "in" => Some("in"),
+        "and" => Some("&&"),
+        "or" => Some("||"),
+        "not" => Some("!"),
+        // bitwise not, spelled like the C++ alternative token since "~" is unary minus
+        "compl" => Some("compl"),
+        // casts, which are always followed by a type ("x as INT")
+        "as" => Some("as"),
+        _ => None,
     }
 }
 
 // language constants
-pub fn from_constant(constant: &String) -> Option<RickrollObject> {
-    match &constant[..] {
+pub fn from_constant(constant: &str) -> Option<RickrollObject> {
+    match constant {
         "TRUE" => Some(RickrollObject::Bool(true)),
         "FALSE" => Some(RickrollObject::Bool(false)),
         "UNDEFINED" => Some(RickrollObject::Undefined),
@@ -80,33 +159,85 @@ pub fn from_constant(constant: &String) -> Option<RickrollObject> {
     }
 }
 
+// where the parser found a variable, so the interpreter can skip looking it up by name
+// depth counts contexts outwards from the innermost one, index is its position in that context
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+// a variable used by an expression or statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct Var {
+    pub name: String,
+    pub slot: Option<Slot>, // None if it wasn't resolved
+}
+
 // variable cache for a single block
-#[derive(Debug, Clone)]
+// variables are kept in the order they were declared, which gives them their slots
+#[derive(Debug, Clone, Default)]
 pub struct Context {
-    vars: HashMap<String, RickrollObject>,
+    names: Vec<String>,
+    values: Vec<RickrollObject>,
+    size: usize, // estimated size of all values in bytes
+    track_size: bool, // whether size is kept up to date, since estimating it walks arrays
 }
 
 impl Context {
     pub fn new() -> Context {
-        Context {
-            vars: HashMap::new(),
+        Context::default()
+    }
+
+    // keeps track of the estimated size of the values (off by default)
+    pub fn with_size_tracking(mut self, track_size: bool) -> Context {
+        self.track_size = track_size;
+        self
+    }
+
+    fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|x| x == name)
+    }
+
+    fn set_index(&mut self, index: usize, value: RickrollObject) {
+        if self.track_size {
+            self.size += value.size_estimate();
+            self.size -= self.values[index].size_estimate();
         }
+        self.values[index] = value;
     }
 
     pub fn set_var(&mut self, name: String, value: RickrollObject) {
-        self.vars.insert(name, value);
+        match self.index_of(&name) {
+            Some(index) => self.set_index(index, value),
+            None => {
+                if self.track_size {
+                    self.size += value.size_estimate();
+                }
+                self.names.push(name);
+                self.values.push(value);
+            }
+        }
+    }
+
+    // 0 unless size tracking is on
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // removes every variable but keeps the allocations, so the context can be reused
+    pub fn clear(&mut self) {
+        self.names.clear();
+        self.values.clear();
+        self.size = 0;
     }
 
     pub fn get_var(&self, name: String) -> Option<RickrollObject> {
-        if self.vars.contains_key(&name) {
-            return Some(self.vars.get(&name).unwrap().clone());
-        } else {
-            return None;
-        }
+        return self.index_of(&name).map(|index| self.values[index].clone());
     }
 
     pub fn has_var(&self, name: String) -> bool {
-        self.vars.contains_key(&name)
+        self.index_of(&name).is_some()
     }
 }
 
@@ -115,6 +246,12 @@ pub struct Scope {
     contexts: Vec<Context>,
 }
 
+impl Default for Scope {
+    fn default() -> Scope {
+        Scope::new()
+    }
+}
+
 impl Scope {
     pub fn new() -> Scope {
         Scope {
@@ -129,6 +266,8 @@ impl Scope {
         Scope { contexts }
     }
 
+    // a scope always has its global context, so it is never empty
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         self.contexts.len()
     }
@@ -158,35 +297,87 @@ impl Scope {
             .expect("Cannot pop context from empty scope")
     }
 
+    // finds the innermost variable with the name, as (context, index)
+    fn find(&self, name: &str) -> Option<(usize, usize)> {
+        for (ind, context) in self.contexts.iter().enumerate().rev() {
+            if let Some(index) = context.index_of(name) {
+                return Some((ind, index));
+            }
+        }
+        return None;
+    }
+
+    // finds a variable using its slot, or by name if it wasn't resolved
+    // the parser mirrors the contexts of the interpreter, so a slot always holds its variable
+    fn locate(&self, var: &Var) -> Option<(usize, usize)> {
+        match var.slot {
+            Some(slot) => {
+                let ind = self.contexts.len() - 1 - slot.depth;
+                // a global declared after the intro returned doesn't exist
+                if slot.index >= self.contexts[ind].names.len() {
+                    return None;
+                }
+                return Some((ind, slot.index));
+            }
+            None => return self.find(&var.name),
+        }
+    }
+
+    // the slot of the variable that a name refers to, None if it doesn't exist
+    pub fn resolve(&self, name: &str) -> Option<Slot> {
+        return self.find(name).map(|(ind, index)| Slot {
+            depth: self.contexts.len() - 1 - ind,
+            index,
+        });
+    }
+
+    // gets the value of a resolved variable, None if it doesn't exist
+    pub fn get(&self, var: &Var) -> Option<RickrollObject> {
+        return self
+            .locate(var)
+            .map(|(ind, index)| self.contexts[ind].values[index].clone());
+    }
+
+    // sets a resolved variable, returns false if it doesn't exist
+    pub fn set(&mut self, var: &Var, value: RickrollObject) -> bool {
+        match self.locate(var) {
+            Some((ind, index)) => {
+                self.contexts[ind].set_index(index, value);
+                return true;
+            }
+            None => return false,
+        }
+    }
+
     // sets a variable in the scope
     // does nothing if variable doesn't exist
     pub fn set_var(&mut self, name: String, value: RickrollObject) {
-        for context in self.contexts.iter_mut().rev() {
-            if context.has_var(name.clone()) {
-                context.set_var(name, value);
-                return;
-            }
+        if let Some((ind, index)) = self.find(&name) {
+            self.contexts[ind].set_index(index, value);
         }
     }
 
     // gets the value of a variable in the scope
     // returns None if variable doesn't exist
     pub fn get_var(&self, name: String) -> Option<RickrollObject> {
-        for context in self.contexts.iter().rev() {
-            if context.has_var(name.clone()) {
-                return Some(context.get_var(name).unwrap());
-            }
-        }
-        return None;
+        return self
+            .find(&name)
+            .map(|(ind, index)| self.contexts[ind].values[index].clone());
+    }
+
+    // whether a name refers to a variable of the global context
+    // false if an inner context has a variable with the same name
+    pub fn is_global(&self, name: String) -> bool {
+        return matches!(self.find(&name), Some((0, _)));
+    }
+
+    // estimated size of all values in the scope in bytes
+    pub fn size(&self) -> usize {
+        self.contexts.iter().map(|x| x.size()).sum()
     }
 
     pub fn has_var(&self, name: String) -> bool {
-        for context in self.contexts.iter() {
-            if context.has_var(name.clone()) {
-                return true;
-            }
-        }
-        return false;
+        return self.find(&name).is_some();
     }
 
     pub fn add_var(&mut self, name: String) {
@@ -0,0 +1,168 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+// runs the rickroll binary with the arguments, writing stdin to it
+fn rickroll(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rickroll"))
+        .args(args)
+        .env_remove("NO_COLOR")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Could not start rickroll");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .expect("Could not write to rickroll");
+    return child.wait_with_output().expect("Could not wait for rickroll");
+}
+
+fn example(name: &str) -> String {
+    let path: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples").join(name);
+    return path.to_string_lossy().to_string();
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[test]
+fn errors_are_not_colored_when_redirected() {
+    let output = rickroll(&[&example("divide_by_zero.rick")], "");
+    let err = stderr(&output);
+    assert_eq!(err, "Runtime Error: Division by zero\nTraceback on line 3\n");
+    assert!(!err.contains('\u{1b}'), "escape codes in {:?}", err);
+}
+
+#[test]
+fn no_color_flag_disables_color() {
+    let output = rickroll(&["--no-color", &example("divide_by_zero.rick")], "");
+    assert!(!stderr(&output).contains('\u{1b}'));
+}
+
+// the events printed by --debug-json, with one JSON object per line of stderr
+fn json_events(output: &Output) -> Vec<serde_json::Value> {
+    return stderr(output)
+        .lines()
+        .map(|line| match serde_json::from_str(line) {
+            Ok(event) => event,
+            Err(err) => panic!("{:?} is not JSON: {}", line, err),
+        })
+        .collect();
+}
+
+#[test]
+fn debug_json_events_cover_each_stage() {
+    let output = rickroll(&["--debug-json", &example("fib.rick")], "");
+    assert!(output.status.success());
+    let events = json_events(&output);
+    let stages: Vec<(&str, &str)> = events
+        .iter()
+        .map(|event| (event["stage"].as_str().unwrap(), event["message"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        stages,
+        vec![
+            ("lex", "Started lexing"),
+            ("lex", "Finished lexing"),
+            ("parse", "Started parsing"),
+            ("parse", "Finished parsing"),
+            ("execute", "Started executing"),
+            ("execute", "Finished executing"),
+        ]
+    );
+    assert!(events[1]["token_count"].as_u64().unwrap() > 0);
+    for event in events.iter().filter(|event| event["message"].as_str().unwrap().starts_with("Finished")) {
+        assert!(event["duration_ms"].as_f64().unwrap() >= 0.0);
+    }
+}
+
+#[test]
+fn debug_json_reports_errors() {
+    let output = rickroll(&["--debug-json", &example("divide_by_zero.rick")], "");
+    assert_eq!(output.status.code(), Some(1));
+    let events = json_events(&output);
+    let last = events.last().unwrap();
+    assert_eq!(last["stage"], "execute");
+    assert_eq!(last["message"], "Failed executing");
+    assert_eq!(last["error"], "Runtime Error: Division by zero\nTraceback on line 3");
+}
+
+#[test]
+fn repl_recovers_from_errors_inside_blocks() {
+    let session = "Never gonna let a down
+Never gonna give a 0
+Inside we both know a == 0
+  Never gonna say 1 / a
+Your heart's been aching but you're too shy to say it
+Never gonna let c down
+Never gonna give c 5
+[Verse show]
+(Ooh give you up)
+Never gonna say c
+
+Never gonna run show and desert you
+";
+    let output = rickroll(&["--repl"], session);
+    let out = String::from_utf8_lossy(&output.stdout).to_string();
+    assert_eq!(stderr(&output), "Runtime Error: Division by zero\nTraceback on line 2\n");
+    // c is declared in the global context, not in the if block left by the error
+    assert!(out.ends_with("> 5\n> "), "{}", out);
+    assert!(output.status.success());
+}
+
+#[test]
+fn errors_exit_with_a_failure_status() {
+    // a syntax error stops the program before it runs
+    let output = rickroll(&[&example("break_outside_loop.rick")], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    // so does a runtime error, after the output before it
+    let output = rickroll(&[&example("divide_by_zero.rick")], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+}
+
+#[test]
+fn programs_without_errors_exit_successfully() {
+    let output = rickroll(&[&example("fib.rick")], "");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn repl_keeps_aliases_between_inputs() {
+    let session = "You know the rules: shout means Never gonna say
+shout 1 + 2
+You know the rules: shout means Never gonna say
+";
+    let output = rickroll(&["--repl"], session);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "> > 3\n> > ");
+    assert_eq!(stderr(&output), "Name Error on line 1: Alias shout already exists\n");
+}
+
+#[test]
+fn debug_repl_follows_a_scripted_session() {
+    let script = "break 17\nc\np a\np b\ns\nq\n";
+    let output = rickroll(&["--debug-repl", &example("fib.rick")], script);
+    let transcript = "line 15: Never gonna let a down
+(debug) Breakpoint on line 17
+(debug) line 17: (Ooh give you a) Never gonna run fib and desert a
+(debug) a = 10
+(debug) No variable b
+(debug) line 3: Inside we both know a <= 1
+(debug) ";
+    assert_eq!(stderr(&output), transcript);
+    // quitting stops the program before it prints anything
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn debug_repl_runs_to_the_end_without_breakpoints() {
+    let output = rickroll(&["--debug-repl", &example("fib.rick")], "c\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "55\n");
+    assert!(output.status.success());
+}
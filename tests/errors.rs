@@ -0,0 +1,24 @@
+use rickroll::error::{Error, ErrorType};
+
+use std::error::Error as _;
+
+#[test]
+fn source_walks_the_tracebacks() {
+    let root = Error::new(ErrorType::RuntimeError, "Division by zero", Some(3));
+    let err = Error::traceback(Error::traceback(root, Some(8)), Some(12));
+    let mut lines: Vec<Option<usize>> = vec![err.line()];
+    let mut cur = err.source();
+    while let Some(source) = cur {
+        let source = source.downcast_ref::<Error>().expect("source should be an Error");
+        lines.push(source.line());
+        cur = source.source();
+    }
+    assert_eq!(lines, vec![Some(12), Some(8), Some(3)]);
+}
+
+#[test]
+fn original_error_has_no_source() {
+    let err = Error::new(ErrorType::NameError, "Variable x doesn't exist", Some(2));
+    assert!(err.source().is_none());
+    assert_eq!(err.to_string(), "Name Error on line 2: Variable x doesn't exist");
+}
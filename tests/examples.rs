@@ -0,0 +1,35 @@
+use rickroll::testing::{run_tests, run_tests_with_interrupt};
+
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+// runs every program in examples against its expected output, like `rickroll --test examples`
+#[test]
+fn examples_match_expected_output() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let results = match run_tests(&dir) {
+        Ok(results) => results,
+        Err(err) => panic!("Could not run the examples:\n{}", err),
+    };
+    assert!(!results.is_empty(), "no examples found in {}", dir.display());
+    let failures: Vec<String> = results
+        .iter()
+        .filter_map(|res| {
+            res.failure
+                .as_ref()
+                .map(|msg| format!("{}: {}", res.program.display(), msg))
+        })
+        .collect();
+    assert!(failures.is_empty(), "{} examples failed:\n{}", failures.len(), failures.join("\n"));
+}
+
+// an interrupted test run fails the test that was running and skips the rest
+#[test]
+fn interrupted_runs_stop_after_one_test() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let interrupt = Arc::new(AtomicBool::new(true));
+    let results = run_tests_with_interrupt(&dir, &interrupt).expect("the examples should be readable");
+    assert_eq!(results.len(), 1);
+    assert!(results[0].failure.as_ref().is_some_and(|msg| msg.contains("Interrupted")));
+}
@@ -0,0 +1,19 @@
+use rickroll::util::RickrollObject;
+
+#[test]
+fn literals_are_parsed_like_in_programs() {
+    assert_eq!(RickrollObject::from_literal("42"), Some(RickrollObject::Int(42)));
+    assert_eq!(RickrollObject::from_literal("2.5"), Some(RickrollObject::Float(2.5)));
+    assert_eq!(RickrollObject::from_literal("TRUE"), Some(RickrollObject::Bool(true)));
+    assert_eq!(RickrollObject::from_literal("'a'"), Some(RickrollObject::Char('a')));
+    assert_eq!(RickrollObject::from_literal("UNDEFINED"), Some(RickrollObject::Undefined));
+}
+
+#[test]
+fn other_expressions_are_not_literals() {
+    assert_eq!(RickrollObject::from_literal("\"ab\""), None);
+    assert_eq!(RickrollObject::from_literal("~1"), None);
+    assert_eq!(RickrollObject::from_literal("1 + 2"), None);
+    assert_eq!(RickrollObject::from_literal("x"), None);
+    assert_eq!(RickrollObject::from_literal(""), None);
+}
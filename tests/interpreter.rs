@@ -0,0 +1,284 @@
+use rickroll::interpreter::Interpreter;
+use rickroll::lexer::Lexer;
+use rickroll::parser::Parser;
+use rickroll::util::RickrollObject;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+// lexes and parses a program that is known to be valid
+fn interpreter(src: &str) -> Interpreter {
+    let tokens = Lexer::new(String::from(src)).parse().expect("program should lex");
+    let functions = Parser::new(tokens).parse().expect("program should parse");
+    return Interpreter::new(functions);
+}
+
+// like interpreter, with INT conditions allowed in both the parser and the interpreter
+fn int_conditions_interpreter(src: &str) -> Interpreter {
+    let tokens = Lexer::new(String::from(src)).parse().expect("program should lex");
+    let functions = Parser::new(tokens)
+        .with_int_conditions(true)
+        .parse()
+        .expect("program should parse");
+    return Interpreter::new(functions).with_int_conditions(true);
+}
+
+const SLEEPER: &str = "[Chorus]
+Never gonna say 1
+Never gonna run Sleep and desert 1
+Never gonna say 2
+";
+
+#[test]
+fn sleep_returns_when_allowed() {
+    let (output, _) = interpreter(SLEEPER).run_to_string("").unwrap();
+    assert_eq!(output, "1\n2\n");
+}
+
+#[test]
+fn sleep_fails_when_disabled() {
+    let err = interpreter(SLEEPER).with_sleep(false).run_to_string("").unwrap_err();
+    assert_eq!(err.message(), "Sleep is disabled");
+    assert_eq!(err.line(), Some(3));
+}
+
+#[test]
+fn globals_are_kept_after_run() {
+    let src = "[Intro]
+Never gonna let answer down
+
+[Chorus]
+Never gonna give answer 6 * 7
+";
+    let mut interpreter = interpreter(src);
+    interpreter.run_to_string("").unwrap();
+    let globals = interpreter.last_globals();
+    assert_eq!(globals.get_var(String::from("answer")), Some(RickrollObject::Int(42)));
+    assert_eq!(globals.len(), 1);
+}
+
+const BIG_GLOBAL: &str = "[Intro]
+Never gonna let xs down
+
+[Chorus]
+(Ooh give you xs) Never gonna run ArrayFill and desert 0, 100
+";
+
+#[test]
+fn memory_is_estimated_only_when_limited() {
+    let mut unlimited = interpreter(BIG_GLOBAL);
+    unlimited.run_to_string("").unwrap();
+    assert_eq!(unlimited.last_globals().size(), 0);
+    let mut limited = interpreter(BIG_GLOBAL).with_max_memory(Some(1 << 20));
+    limited.run_to_string("").unwrap();
+    assert!(limited.last_globals().size() > 0);
+}
+
+const TRIPLE: &str = "[Verse triple]
+(Ooh give you x)
+(Ooh) Never gonna give, never gonna give (give you x, x, x)
+
+[Chorus]
+Never gonna let xs down
+(Ooh give you xs) Never gonna run triple and desert 1
+Never gonna say xs
+";
+
+#[test]
+fn collection_size_allows_small_returned_arrays() {
+    let (output, _) = interpreter(TRIPLE)
+        .with_max_collection_size(Some(3))
+        .run_to_string("")
+        .unwrap();
+    assert_eq!(output, "[1, 1, 1]\n");
+}
+
+#[test]
+fn collection_size_limits_returned_arrays() {
+    let err = interpreter(TRIPLE)
+        .with_max_collection_size(Some(2))
+        .run_to_string("")
+        .unwrap_err();
+    assert_eq!(err.message(), "Array length 3 exceeds the maximum of 2");
+    // the return fails, below the traceback of the call
+    assert_eq!(err.line(), Some(7));
+    assert!(err.to_string().starts_with("Runtime Error: Array length 3 exceeds the maximum of 2\nTraceback on line 3\n"));
+}
+
+const COUNTDOWN: &str = "[Chorus]
+Never gonna let n down
+Never gonna give n 3
+Inside we both know n
+  Never gonna say n
+  Never gonna turn n down
+We know the game and we're gonna play it
+Inside we both know n
+  Never gonna say \"not printed\"
+Your heart's been aching but you're too shy to say it
+Inside we both know 1
+  Never gonna say \"constant\"
+Your heart's been aching but you're too shy to say it
+";
+
+#[test]
+fn int_conditions_are_truthy_when_enabled() {
+    let (output, _) = int_conditions_interpreter(COUNTDOWN).run_to_string("").unwrap();
+    assert_eq!(output, "3\n2\n1\nconstant\n");
+}
+
+#[test]
+fn int_conditions_fail_by_default() {
+    // the constant condition is rejected before the program runs
+    let tokens = Lexer::new(String::from(COUNTDOWN)).parse().unwrap();
+    let err = Parser::new(tokens).parse().unwrap_err();
+    assert_eq!(err.message(), "If condition is not boolean");
+    assert_eq!(err.line(), Some(11));
+    // a variable is only checked when the condition is evaluated
+    let src = COUNTDOWN.split("Inside we both know 1").next().unwrap();
+    let err = interpreter(src).run_to_string("").unwrap_err();
+    assert_eq!(err.message(), "While condition is not boolean");
+    assert_eq!(err.line(), Some(4));
+}
+
+#[test]
+fn lossy_input_replaces_invalid_utf8() {
+    // the same program and input as the strict examples/invalid_utf8 test
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let src = fs::read_to_string(dir.join("invalid_utf8.rick")).unwrap();
+    let input = fs::read(dir.join("invalid_utf8.in")).unwrap();
+    assert!(String::from_utf8(input.clone()).is_err(), "the input should be invalid UTF-8");
+    let mut output: Vec<u8> = Vec::new();
+    interpreter(&src)
+        .with_lossy_input(true)
+        .run(&mut output, &mut BufReader::new(&input[..]))
+        .unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "ab\u{FFFD}c\n");
+}
+
+#[test]
+fn printed_values_are_collected_with_their_lines() {
+    let src = "[Chorus]
+Never gonna let x down
+Never gonna give x 3
+Never gonna say x * 2
+Never gonna say \"ab\"
+";
+    let mut interpreter = interpreter(src).with_collect_printed(true);
+    let (output, _) = interpreter.run_to_string("").unwrap();
+    assert_eq!(output, "6\nab\n");
+    let printed = interpreter.printed_values();
+    assert_eq!(printed.len(), 2);
+    assert_eq!(printed[0], (4, RickrollObject::Int(6)));
+    assert_eq!(printed[1].0, 5);
+    assert_eq!(printed[1].1.to_string(), "[a, b]");
+}
+
+#[test]
+fn printed_values_are_not_collected_by_default() {
+    let mut interpreter = interpreter("[Chorus]\nNever gonna say 1\n");
+    interpreter.run_to_string("").unwrap();
+    assert!(interpreter.printed_values().is_empty());
+}
+
+#[test]
+fn run_to_string_returns_the_output_and_result() {
+    let src = "[Chorus]
+Never gonna let name down
+(Ooh give you name) Never gonna run ReadLine and desert you
+Never gonna say name
+Never gonna say 6 * 7
+(Ooh) Never gonna give, never gonna give (give you 42)
+";
+    let (output, res) = interpreter(src).run_to_string("rick\n").unwrap();
+    assert_eq!(output, "rick\n42\n");
+    assert_eq!(res, RickrollObject::Int(42));
+}
+
+#[test]
+fn interrupted_programs_stop_before_the_next_statement() {
+    let flag = Arc::new(AtomicBool::new(true));
+    let err = interpreter(SLEEPER).with_interrupt(flag).run_to_string("").unwrap_err();
+    assert_eq!(format!("{}", err), "Runtime Error on line 2: Interrupted");
+}
+
+#[test]
+fn uninterrupted_programs_run_to_the_end() {
+    let flag = Arc::new(AtomicBool::new(false));
+    let (output, _) = interpreter(SLEEPER).with_interrupt(flag).run_to_string("").unwrap();
+    assert_eq!(output, "1\n2\n");
+}
+
+#[test]
+fn globals_after_an_intro_return_do_not_exist() {
+    let src = "[Intro]
+Never gonna let a down
+Never gonna give a 1
+(Ooh) Never gonna give, never gonna give (give you 0)
+Never gonna let b down
+
+[Chorus]
+Never gonna say a
+Never gonna say b
+";
+    let err = interpreter(src).run_to_string("").unwrap_err();
+    assert_eq!(err.message(), "Variable b doesn't exist");
+}
+
+#[test]
+fn stepping_a_global_after_an_intro_return_fails() {
+    let src = "[Intro]
+Never gonna let a down
+(Ooh) Never gonna give, never gonna give (give you 0)
+Never gonna let b down
+
+[Verse bump]
+(Ooh give you up)
+Never gonna turn b up
+
+[Chorus]
+Never gonna run bump and desert you
+";
+    let err = interpreter(src).run_to_string("").unwrap_err();
+    assert_eq!(format!("{}", err), "Name Error on line 8: Variable b doesn't exist\nTraceback on line 11");
+}
+
+#[test]
+fn assigning_a_global_after_an_intro_return_fails() {
+    let src = "[Intro]
+Never gonna let a down
+(Ooh) Never gonna give, never gonna give (give you 0)
+Never gonna let b down
+
+[Verse fill]
+(Ooh give you up)
+Never gonna give b 2
+
+[Chorus]
+Never gonna run fill and desert you
+";
+    let err = interpreter(src).run_to_string("").unwrap_err();
+    assert_eq!(format!("{}", err), "Name Error on line 8: Variable b doesn't exist\nTraceback on line 11");
+}
+
+#[test]
+fn inputs_stopped_by_an_error_keep_the_slots_of_their_globals() {
+    let mut parser = Parser::new(Vec::new());
+    let mut interpreter = Interpreter::new(HashMap::new());
+    let mut run = |src: &str| {
+        let tokens = Lexer::new(String::from(src)).parse().expect("input should lex");
+        let (functions, statements) = parser.parse_input(tokens).unwrap().expect("input should be complete");
+        let mut output: Vec<u8> = Vec::new();
+        let res = interpreter.run_input(functions, &statements, &mut output, &mut BufReader::new(&b""[..]));
+        return (String::from_utf8_lossy(&output).to_string(), res);
+    };
+    let (_, res) = run("Never gonna let a down\nNever gonna say 1 / 0\nNever gonna let b down\n");
+    assert!(res.is_err());
+    // c comes after b, which was skipped by the error
+    let (output, res) = run("Never gonna let c down\nNever gonna give c 3\nNever gonna say c\nNever gonna say b\n");
+    assert!(res.is_ok());
+    assert_eq!(output, "3\nUNDEFINED\n");
+}
@@ -0,0 +1,145 @@
+use rickroll::lexer::Lexer;
+
+// the tokens of a program in their debug form, which includes their lines
+fn tokens(src: &str) -> Vec<String> {
+    match Lexer::new(String::from(src)).parse() {
+        Ok(tokens) => return tokens.iter().map(|token| format!("{:?}", token)).collect(),
+        Err(err) => panic!("{:?} should lex:\n{}", src, err),
+    }
+}
+
+// a program that is lexed with each kind of line ending
+const PROGRAM: &str = "[Chorus]
+Never gonna let a down
+Never gonna give a 3 + 4
+Inside we both know a > 5
+  Never gonna say a
+Your heart's been aching but you're too shy to say it
+";
+
+// a program with blank lines where WITH_COMMENTS has comments
+const WITHOUT_COMMENTS: &str = "
+[Chorus]
+Never gonna let a down
+
+Never gonna give a 3 + 4
+
+Inside we both know a > 5
+  Never gonna say a
+
+
+Your heart's been aching but you're too shy to say it
+";
+
+const WITH_COMMENTS: &str = "# a comment before the program
+[Chorus]
+Never gonna let a down
+We're no strangers to love
+Never gonna give a 3 + 4
+  # an indented comment
+Inside we both know a > 5
+  Never gonna say a
+    We're no strangers to love, you know the rules and so do I
+#
+Your heart's been aching but you're too shy to say it
+";
+
+#[test]
+fn comments_are_skipped() {
+    // comments keep their lines, so the tokens have the same line numbers
+    assert_eq!(tokens(WITH_COMMENTS), tokens(WITHOUT_COMMENTS));
+    assert!(tokens(WITH_COMMENTS).len() > 5);
+}
+
+
+#[test]
+fn crlf_lexes_like_lf() {
+    assert_eq!(tokens(&PROGRAM.replace('\n', "\r\n")), tokens(PROGRAM));
+}
+
+#[test]
+fn lone_cr_lexes_like_lf() {
+    assert_eq!(tokens(&PROGRAM.replace('\n', "\r")), tokens(PROGRAM));
+}
+
+#[test]
+fn mixed_line_endings_lex_like_lf() {
+    let endings = ["\r\n", "\r", "\n"];
+    let mixed: String = PROGRAM
+        .lines()
+        .enumerate()
+        .map(|(ind, line)| format!("{}{}", line, endings[ind % endings.len()]))
+        .collect();
+    assert_eq!(tokens(&mixed), tokens(PROGRAM));
+}
+
+#[test]
+fn line_ending_examples_keep_their_endings() {
+    // the examples are only useful if git doesn't convert their line endings
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let crlf = std::fs::read_to_string(dir.join("crlf.rick")).unwrap();
+    assert!(crlf.contains("\r\n"));
+    let cr = std::fs::read_to_string(dir.join("cr.rick")).unwrap();
+    assert!(cr.contains('\r') && !cr.contains('\n'));
+}
+
+#[test]
+fn aliases_expand_statements_and_expressions() {
+    let with_aliases = "You know the rules: shout means Never gonna say
+You know the rules: answer means 6 * 7
+[Chorus]
+shout answer
+";
+    let expanded = "
+[Chorus]
+Never gonna say 6 * 7
+";
+    assert_eq!(tokens(with_aliases), tokens(&format!("\n{}", expanded)));
+}
+
+#[test]
+fn aliases_cant_be_keywords() {
+    for name in ["you", "give", "Never", "TRUE", "and"] {
+        let src = format!("You know the rules: {} means 1\n", name);
+        let err = Lexer::new(src).parse().unwrap_err();
+        assert_eq!(err.message(), format!("Alias {} is a keyword", name));
+        assert_eq!(err.line(), Some(1));
+    }
+}
+
+#[test]
+fn aliases_carry_over_to_later_sources() {
+    let (_, aliases) = Lexer::new(String::from("You know the rules: shout means Never gonna say\n"))
+        .parse_with_aliases()
+        .unwrap();
+    let later = Lexer::new(String::from("shout 1\n")).with_aliases(aliases).parse().unwrap();
+    let expected = Lexer::new(String::from("Never gonna say 1\n")).parse().unwrap();
+    assert_eq!(format!("{:?}", later), format!("{:?}", expected));
+}
+
+// the message of the error of a program that shouldn't lex
+fn lex_error(src: &str) -> String {
+    match Lexer::new(String::from(src)).parse() {
+        Ok(_) => panic!("{:?} shouldn't lex", src),
+        Err(err) => return err.message().to_string(),
+    }
+}
+
+#[test]
+fn trailing_commas_are_ignored_in_parameter_lists() {
+    let with_comma = "[Verse add]\n(Ooh give you a, b,)\nNever gonna say a + b\n";
+    let without_comma = "[Verse add]\n(Ooh give you a, b)\nNever gonna say a + b\n";
+    assert_eq!(tokens(with_comma), tokens(without_comma));
+}
+
+#[test]
+fn blank_names_between_commas_are_errors() {
+    let src = "[Verse add]\n(Ooh give you a,,b)\nNever gonna say a + b\n";
+    assert_eq!(lex_error(src), "Blank variable name at position 2");
+}
+
+#[test]
+fn a_lone_comma_is_a_blank_name() {
+    let src = "[Verse nothing]\n(Ooh give you ,)\nNever gonna say 1\n";
+    assert_eq!(lex_error(src), "Blank variable name at position 1");
+}
@@ -0,0 +1,53 @@
+use rickroll::lexer::Lexer;
+use rickroll::parser::{ParsedInput, Parser};
+
+fn parse_input(parser: &mut Parser, src: &str) -> Result<Option<ParsedInput>, rickroll::error::Error> {
+    let tokens = Lexer::new(String::from(src)).parse().expect("input should lex");
+    return parser.parse_input(tokens);
+}
+
+#[test]
+fn interactive_globals_are_checked_across_inputs() {
+    let mut parser = Parser::new(Vec::new());
+    parse_input(&mut parser, "Never gonna let g down\n").unwrap();
+    let err = parse_input(&mut parser, "Never gonna give g g + 1\n").unwrap_err();
+    assert_eq!(err.message(), "Global g used in its own initializer while undefined");
+    assert_eq!(err.line(), Some(1));
+    // once the global has a value, it can build on it
+    parse_input(&mut parser, "Never gonna give g 1\n").unwrap();
+    parse_input(&mut parser, "Never gonna give g g + 1\n").unwrap();
+}
+
+#[test]
+fn verses_are_not_checked_for_initializers() {
+    // the verse may run after the intro gives g a value in another function
+    let src = "[Intro]
+Never gonna let g down
+
+[Verse bump]
+(Ooh give you up)
+Never gonna give g g + 1
+";
+    let tokens = Lexer::new(String::from(src)).parse().unwrap();
+    assert!(Parser::new(tokens).parse().is_ok());
+}
+
+#[test]
+fn open_blocks_wait_for_more_input() {
+    let mut parser = Parser::new(Vec::new());
+    let open = "Inside we both know TRUE\n  Never gonna say 1\n";
+    assert!(parse_input(&mut parser, open).unwrap().is_none());
+    assert!(parser.is_incomplete());
+    let closed = format!("{}Your heart's been aching but you're too shy to say it\n", open);
+    let (_, statements) = parse_input(&mut parser, &closed).unwrap().unwrap();
+    assert_eq!(statements.len(), 1);
+    assert!(!parser.is_incomplete());
+}
+
+#[test]
+fn unbalanced_ends_are_errors() {
+    let mut parser = Parser::new(Vec::new());
+    let err = parse_input(&mut parser, "Your heart's been aching but you're too shy to say it\n").unwrap_err();
+    assert_eq!(err.message(), "Unbalanced statements");
+    assert!(!parser.is_incomplete());
+}
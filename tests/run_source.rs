@@ -0,0 +1,98 @@
+use rickroll::error::{Error, ErrorType};
+use rickroll::stdlib::{Builtin, Builtins};
+use rickroll::util::RickrollObject;
+
+use std::io::{BufRead, Write};
+
+// deep recursion has to fit in the default stack of a test thread
+const DEEP_RECURSION: &str = "[Verse down]
+(Ooh give you n)
+Inside we both know n == 0
+  (Ooh) Never gonna give, never gonna give (give you 0)
+Your heart's been aching but you're too shy to say it
+Never gonna let m down
+Never gonna give m n - 1
+(Ooh give you m) Never gonna run down and desert m
+(Ooh) Never gonna give, never gonna give (give you m + 1)
+
+[Chorus]
+Never gonna let x down
+Never gonna give x 9000
+(Ooh give you x) Never gonna run down and desert x
+Never gonna say x
+";
+
+#[test]
+fn deep_recursion_runs_on_a_default_stack() {
+    let output = rickroll::run_source(DEEP_RECURSION, "").unwrap();
+    assert_eq!(output, "9000\n");
+}
+
+const ECHO: &str = "[Chorus]
+Never gonna let line down
+(Ooh give you line) Never gonna run ReadLine and desert you
+Never gonna say line
+";
+
+#[test]
+fn returns_what_the_program_printed() {
+    let output = rickroll::run_source(ECHO, "never gonna give you up\n").unwrap();
+    assert_eq!(output, "never gonna give you up\n");
+}
+
+#[test]
+fn returns_runtime_errors() {
+    let src = "[Chorus]\nNever gonna say 7 / 2\nNever gonna say 7 / 0\n";
+    let err = rickroll::run_source(src, "").unwrap_err();
+    assert_eq!(err.message(), "Division by zero");
+    assert_eq!(err.line(), Some(3));
+}
+
+#[test]
+fn returns_parse_errors() {
+    let err = rickroll::run_source("[Chorus]\nNever gonna say x\n", "").unwrap_err();
+    assert_eq!(err.to_string(), "Name Error: No such variable x");
+}
+
+fn double(
+    args: Vec<RickrollObject>,
+    _: &mut dyn Write,
+    _: &mut dyn BufRead,
+) -> Result<RickrollObject, Error> {
+    match args[0] {
+        RickrollObject::Int(x) => return Ok(RickrollObject::Int(x * 2)),
+        _ => return Err(Error::new(ErrorType::RuntimeError, "Double takes an INT", None)),
+    }
+}
+
+fn custom_builtins() -> Builtins {
+    return Builtins::new()
+        .with_function("Double", Builtin::new(double, 1))
+        // replaces the standard builtin
+        .with_function("Abs", Builtin::new(double, 1));
+}
+
+#[test]
+fn custom_builtins_can_be_called() {
+    let src = "[Chorus]
+Never gonna let x down
+(Ooh give you x) Never gonna run Double and desert 21
+Never gonna say x
+Never gonna say Double(Abs(~1))
+";
+    let output = rickroll::run_source_with_builtins(src, "", &custom_builtins()).unwrap();
+    assert_eq!(output, "42\n-4\n");
+}
+
+#[test]
+fn custom_builtins_are_checked_before_running() {
+    let src = "[Chorus]
+Never gonna say 1
+Never gonna run Double and desert 1, 2
+";
+    let err = rickroll::run_source_with_builtins(src, "", &custom_builtins()).unwrap_err();
+    assert_eq!(err.to_string(), "Syntax Error on line 3: Wrong number of arguments for Double, expected 1 but found 2");
+    // without the registry, the name is unknown
+    let err = rickroll::run_source(src, "").unwrap_err();
+    assert_eq!(err.message(), "Function name Double doesn't exist");
+}
@@ -0,0 +1,34 @@
+use rickroll::stdlib::BUILTIN_FUNCTIONS;
+use rickroll::util::RickrollObject;
+
+// the parser checks calls written in a program, but builtins still check what they are passed
+#[test]
+fn builtins_check_their_arguments_at_runtime() {
+    let min = BUILTIN_FUNCTIONS["Min"];
+    let mut output: Vec<u8> = Vec::new();
+    let mut input: &[u8] = &[];
+    let err = (min.function)(vec![RickrollObject::Float(1.5)], &mut output, &mut input).unwrap_err();
+    assert_eq!(err.message(), "Wrong number of arguments for Min");
+    assert_eq!(min.arity, Some(2));
+}
+
+#[test]
+fn arity_allows_optional_args() {
+    let assert = BUILTIN_FUNCTIONS["Assert"];
+    assert!(assert.check_arity("Assert", 1, None).is_ok());
+    assert!(assert.check_arity("Assert", 2, None).is_ok());
+    let err = assert.check_arity("Assert", 3, Some(4)).unwrap_err();
+    assert_eq!(err.message(), "Wrong number of arguments for Assert, expected 1 to 2 but found 3");
+    assert_eq!(err.line(), Some(4));
+}
+
+#[test]
+fn exit_status_must_be_an_exit_code() {
+    let exit = BUILTIN_FUNCTIONS["Exit"];
+    let mut output: Vec<u8> = Vec::new();
+    let mut input: &[u8] = &[];
+    let err = (exit.function)(vec![RickrollObject::Int(-1)], &mut output, &mut input).unwrap_err();
+    assert_eq!(format!("{}", err), "Value Error: Exit status -1 is not between 0 and 255");
+    let err = (exit.function)(vec![RickrollObject::Int(255)], &mut output, &mut input).unwrap_err();
+    assert_eq!(err.exit_code(), Some(255));
+}